@@ -0,0 +1,8 @@
+#![no_main]
+
+use goliath_common::AuthMessage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    let _ = AuthMessage::read_from_json(data);
+});
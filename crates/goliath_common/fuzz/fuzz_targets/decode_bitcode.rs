@@ -0,0 +1,20 @@
+#![no_main]
+
+use goliath_common::{BitcodeCodec, EncodedMessage, GoliathCodec};
+use libfuzzer_sys::fuzz_target;
+
+fn encode(codec: &BitcodeCodec, msg: &goliath_common::GoliathMessage) -> Vec<u8> {
+    match codec.encode(msg).expect("Decoded message failed to encode") {
+        EncodedMessage::Binary(data) => data.to_vec(),
+        EncodedMessage::Text(_) => panic!("Bitcode encoded to text"),
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    // Anything that decodes must survive a round trip, compared as bytes since floats may be NaN
+    if let Ok(msg) = BitcodeCodec.decode(data) {
+        let encoded = encode(&BitcodeCodec, &msg);
+        let decoded = BitcodeCodec.decode(&encoded).expect("Encoded message failed to decode");
+        assert_eq!(encode(&BitcodeCodec, &decoded), encoded);
+    }
+});
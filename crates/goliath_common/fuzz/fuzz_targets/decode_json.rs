@@ -0,0 +1,8 @@
+#![no_main]
+
+use goliath_common::{GoliathCodec, JsonCodec};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = JsonCodec.decode(data);
+});
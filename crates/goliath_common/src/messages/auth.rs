@@ -0,0 +1,130 @@
+use crate::GoliathCommand;
+use crate::messages::codec::check_message_size;
+use crate::messages::error::GoliathSerdeError;
+
+// Prepended to the challenge before signing, so the signature is worthless to any other protocol
+const CHALLENGE_SIGNATURE_CONTEXT: &[u8] = b"goliath-auth-v1:";
+
+/// What an authenticated connection is allowed to do on the vehicle, in increasing order
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Observer, // Reports and video only, but can still stop the vehicle
+    Driver,   // Any command, once holding the driving lease
+    Safety,   // Like a driver, but can take the lease from anyone who isn't a safety operator
+}
+
+impl Role {
+    pub fn permits(&self, command: &GoliathCommand) -> bool {
+        match self {
+            Self::Driver | Self::Safety => true,
+            // Heartbeats would keep the watchdog from stopping a vehicle nobody drives
+            Self::Observer => matches!(command, GoliathCommand::EmergencyStop),
+        }
+    }
+
+    /// Whether a credential granted this role can be used to connect as `requested`
+    pub fn allows(&self, requested: Role) -> bool {
+        *self >= requested
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthProof {
+    // Only accepted by vehicles without any authentication configured
+    None,
+    Token(String),
+    // Signature of the challenge, the public key must be in the vehicle's authorized keys
+    Ed25519 {
+        public_key: Vec<u8>,
+        signature: Vec<u8>,
+    },
+}
+
+/// Exchanged as JSON right after the hello: the vehicle sends a challenge, the operator answers
+/// with a request and the vehicle grants it or closes the connection. Skipped when resuming
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMessage {
+    Challenge { nonce: Vec<u8> },
+    Request { role: Role, proof: AuthProof },
+    // The token resumes the session after a dropped connection, without authenticating again
+    Granted { role: Role, resume_token: String },
+}
+
+impl AuthMessage {
+    /// The bytes an Ed25519 proof signs for the given challenge
+    pub fn signed_challenge(nonce: &[u8]) -> Vec<u8> {
+        [CHALLENGE_SIGNATURE_CONTEXT, nonce].concat()
+    }
+
+    pub fn read_from_json(msg: &str) -> Result<Self, GoliathSerdeError> {
+        check_message_size(msg.as_bytes())?;
+        let auth_msg = serde_json::from_str(msg)?;
+        Ok(auth_msg)
+    }
+
+    pub fn into_json(self) -> Result<String, GoliathSerdeError> {
+        let data = serde_json::to_string(&self)?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MotorCommand;
+
+    const ROLES: [Role; 3] = [Role::Observer, Role::Driver, Role::Safety];
+
+    fn commands() -> Vec<GoliathCommand> {
+        vec![
+            GoliathCommand::Motor(MotorCommand::Thrust(0.5)),
+            GoliathCommand::Motor(MotorCommand::TurretHome),
+            GoliathCommand::Heartbeat,
+            GoliathCommand::EmergencyStop,
+            GoliathCommand::ClearEmergencyStop,
+            GoliathCommand::TakeControl,
+            GoliathCommand::ReleaseControl,
+        ]
+    }
+
+    #[test]
+    fn observer_is_only_permitted_to_stop() {
+        for command in commands() {
+            assert_eq!(
+                Role::Observer.permits(&command),
+                command == GoliathCommand::EmergencyStop,
+                "{command:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn drivers_are_permitted_everything() {
+        for role in [Role::Driver, Role::Safety] {
+            for command in commands() {
+                assert!(role.permits(&command), "{role:?} {command:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn roles_only_allow_themselves_and_below() {
+        for granted in ROLES {
+            for requested in ROLES {
+                assert_eq!(
+                    granted.allows(requested),
+                    requested <= granted,
+                    "{granted:?} as {requested:?}"
+                );
+            }
+        }
+        assert!(!Role::Observer.allows(Role::Driver));
+        assert!(!Role::Driver.allows(Role::Safety));
+        assert!(Role::Safety.allows(Role::Observer));
+    }
+}
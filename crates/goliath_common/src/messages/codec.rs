@@ -0,0 +1,84 @@
+use crate::GoliathMessage;
+use crate::messages::error::GoliathSerdeError;
+use bytes::Bytes;
+
+/// Largest encoded message accepted from a peer, also the websocket frame limit.
+/// Far above any legitimate message, small enough that a hostile peer can't exhaust memory
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024;
+
+/// Rejects input before it reaches a deserializer, whose allocations are sized from the input
+pub(crate) fn check_message_size(data: &[u8]) -> Result<(), GoliathSerdeError> {
+    if data.len() > MAX_MESSAGE_SIZE {
+        return Err(GoliathSerdeError::MessageTooLarge {
+            size: data.len(),
+            max: MAX_MESSAGE_SIZE,
+        });
+    }
+
+    Ok(())
+}
+
+/// Encoding of the messages on the control websocket, negotiated during the handshake
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireFormat {
+    #[default]
+    Bitcode, // Sent as binary frames
+    Json, // Sent as text frames, readable from a browser console or websocat
+}
+
+pub enum EncodedMessage {
+    Binary(Bytes),
+    Text(String),
+}
+
+pub trait GoliathCodec: Send + Sync {
+    fn format(&self) -> WireFormat;
+    fn encode(&self, msg: &GoliathMessage) -> Result<EncodedMessage, GoliathSerdeError>;
+    fn decode(&self, data: &[u8]) -> Result<GoliathMessage, GoliathSerdeError>;
+}
+
+pub fn codec_for_format(format: WireFormat) -> Box<dyn GoliathCodec> {
+    match format {
+        WireFormat::Bitcode => Box::new(BitcodeCodec),
+        WireFormat::Json => Box::new(JsonCodec),
+    }
+}
+
+pub struct BitcodeCodec;
+
+impl GoliathCodec for BitcodeCodec {
+    fn format(&self) -> WireFormat {
+        WireFormat::Bitcode
+    }
+
+    fn encode(&self, msg: &GoliathMessage) -> Result<EncodedMessage, GoliathSerdeError> {
+        let data = bitcode::serialize(msg)?;
+        Ok(EncodedMessage::Binary(Bytes::from_owner(data)))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<GoliathMessage, GoliathSerdeError> {
+        check_message_size(data)?;
+        let msg = bitcode::deserialize(data)?;
+        Ok(msg)
+    }
+}
+
+pub struct JsonCodec;
+
+impl GoliathCodec for JsonCodec {
+    fn format(&self) -> WireFormat {
+        WireFormat::Json
+    }
+
+    fn encode(&self, msg: &GoliathMessage) -> Result<EncodedMessage, GoliathSerdeError> {
+        let data = serde_json::to_string(msg)?;
+        Ok(EncodedMessage::Text(data))
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<GoliathMessage, GoliathSerdeError> {
+        check_message_size(data)?;
+        let msg = serde_json::from_slice(data)?;
+        Ok(msg)
+    }
+}
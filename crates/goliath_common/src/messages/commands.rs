@@ -1,30 +1,150 @@
-use crate::messages::error::GoliathSerdeError;
-use bytes::Bytes;
+/// How the drive commands are mixed into the power of each track
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DriveMode {
+    #[default]
+    Arcade, // Thrust and steer
+    Tank,  // Left and right track powers
+    Pivot, // Steer only, turning in place
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Track {
+    Left,
+    Right,
+}
+
+/// Shape of the response of a track to the requested power, applied before trim and deadband
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerCurve {
+    #[default]
+    Linear,
+    // Requested power raised to this exponent, above 1.0 gives finer control at low power
+    Exponential(f32),
+    // Output powers for evenly spaced requested powers from 0.0 to 1.0, linearly interpolated
+    Lookup(Vec<f32>),
+}
+
+/// Corrects a single track's motor, so both tracks respond alike to the same power
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrackCalibration {
+    // Lowest power the motor actually moves at, any non-zero power is mapped above it
+    pub min_power: f32,
+    // Multiplier evening out the tracks, so the vehicle drives straight
+    pub trim: f32,
+    pub curve: PowerCurve,
+}
+
+impl Default for TrackCalibration {
+    fn default() -> Self {
+        Self {
+            min_power: 0.0,
+            trim: 1.0,
+            curve: PowerCurve::Linear,
+        }
+    }
+}
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum MotorCommand {
     Thrust(f32),
     Steer(f32),
-    TurretAngle(f32),
+    LeftTrack(f32),  // Tank mode only, between -1.0 and 1.0
+    RightTrack(f32), // Tank mode only, between -1.0 and 1.0
+    // Both values are applied together, unlike separate thrust and steer commands
+    Drive {
+        thrust: f32,
+        steer: f32,
+    },
+    // Tank mode only, both tracks are applied together
+    Tank {
+        left: f32,
+        right: f32,
+    },
+    SetDriveMode(DriveMode),
+    // Applied until the vehicle restarts, a lasting calibration belongs in the vehicle config
+    Calibrate {
+        track: Track,
+        calibration: TrackCalibration,
+    },
+    TurretAngle(f32), // Degrees, 0 is facing forward
+    TurretRate(f32),  // Fraction of the turret's max speed, between -1.0 and 1.0
+    TurretHome,
 
     // Only manual construction
     #[serde(skip)]
+    Heartbeat,
+    #[serde(skip)]
     End,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+impl MotorCommand {
+    /// Whether the command can set any actuator in motion
+    pub fn is_motion(&self) -> bool {
+        matches!(
+            self,
+            Self::Thrust(_)
+                | Self::Steer(_)
+                | Self::LeftTrack(_)
+                | Self::RightTrack(_)
+                | Self::Drive { .. }
+                | Self::Tank { .. }
+                | Self::TurretAngle(_)
+                | Self::TurretRate(_)
+                | Self::TurretHome
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub enum GoliathCommand {
     Motor(MotorCommand),
+    // Keeps the vehicle's watchdog from stopping the motors while no other command is sent
+    Heartbeat,
+    // Stops all actuators and rejects motion commands until cleared
+    EmergencyStop,
+    ClearEmergencyStop,
+    // Only the connection holding the driving lease may move the vehicle
+    TakeControl,
+    ReleaseControl,
 }
 
 impl GoliathCommand {
-    pub fn read_from_bytes(msg_bytes: &[u8]) -> Result<Self, GoliathSerdeError> {
-        let cmd = bitcode::deserialize(msg_bytes)?;
-        Ok(cmd)
+    /// Commands the operator should know the fate of, the vehicle acknowledges them
+    pub fn needs_ack(&self) -> bool {
+        matches!(
+            self,
+            Self::EmergencyStop
+                | Self::ClearEmergencyStop
+                | Self::TakeControl
+                | Self::ReleaseControl
+                | Self::Motor(
+                    MotorCommand::SetDriveMode(_)
+                        | MotorCommand::Calibrate { .. }
+                        | MotorCommand::TurretHome
+                )
+        )
+    }
+
+    /// Commands only accepted from the connection holding the driving lease
+    pub fn needs_lease(&self) -> bool {
+        !matches!(
+            self,
+            Self::EmergencyStop | Self::TakeControl | Self::ReleaseControl
+        )
     }
 
-    pub fn into_bytes(self) -> Result<Bytes, GoliathSerdeError> {
-        let data = bitcode::serialize(&self)?;
-        Ok(Bytes::from_owner(data))
+    /// Continuous control, where a late or out of order command is worse than a missing one
+    pub fn is_control(&self) -> bool {
+        match self {
+            Self::Heartbeat => true,
+            Self::Motor(motor_cmd) => {
+                motor_cmd.is_motion() && !matches!(motor_cmd, MotorCommand::TurretHome)
+            }
+            _ => false,
+        }
     }
 }
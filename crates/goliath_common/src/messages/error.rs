@@ -3,6 +3,9 @@ pub enum GoliathSerdeError {
     #[error("Error deserializing JSON message: {0}")]
     SerdeJsonError(#[from] serde_json::Error),
 
-    #[error("Error deserializing wincode message: {0}")]
+    #[error("Error deserializing bitcode message: {0}")]
     BitcodeError(#[from] bitcode::Error),
+
+    #[error("Message of {size} bytes exceeds the limit of {max} bytes")]
+    MessageTooLarge { size: usize, max: usize },
 }
@@ -0,0 +1,343 @@
+use crate::WireFormat;
+use crate::messages::codec::check_message_size;
+use crate::messages::error::GoliathSerdeError;
+
+/// Bumped on every change to the wire format of commands and reports
+pub const PROTOCOL_VERSION: u32 = 9;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    Turret,
+    Telemetry,
+    VideoH264,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoCodec {
+    H264,
+}
+
+/// Where the operator receives the RTP video, and what it can decode
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct VideoOffer {
+    pub port: u16,
+    // In order of preference
+    pub codecs: Vec<VideoCodec>,
+}
+
+/// The RTP video stream, as the vehicle sends it to the operator
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct VideoTransport {
+    pub port: u16,
+    pub codec: VideoCodec,
+    pub payload_type: u8,
+    pub ssrc: u32,
+}
+
+impl VideoOffer {
+    /// The vehicle's answer, with the operator's preferred codec among the ones it can send
+    pub fn accept(
+        &self,
+        codecs: &[VideoCodec],
+        payload_type: u8,
+        ssrc: u32,
+    ) -> Result<VideoTransport, String> {
+        let codec = self
+            .codecs
+            .iter()
+            .find(|codec| codecs.contains(codec))
+            .copied()
+            .ok_or_else(|| format!("None of the video codecs {:?} is supported", self.codecs))?;
+
+        Ok(VideoTransport {
+            port: self.port,
+            codec,
+            payload_type,
+            ssrc,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BuildInfo {
+    pub version: String,
+    // Only known if the build environment provided it
+    pub commit: Option<String>,
+}
+
+impl BuildInfo {
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            commit: option_env!("GOLIATH_BUILD_COMMIT").map(str::to_string),
+        }
+    }
+}
+
+/// First message sent by each side of a new connection, the operator speaks first.
+/// Always JSON, so peers with a different wire format can still understand each other's hello
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Hello {
+    pub protocol_version: u32,
+    pub build: BuildInfo,
+    // The vehicle answers with the capabilities negotiated for the session
+    pub capabilities: Vec<Capability>,
+    // In order of preference, the vehicle answers with the single format chosen
+    pub wire_formats: Vec<WireFormat>,
+    // Sent by an operator picking a dropped session back up, echoed by the vehicle if it could
+    #[serde(default)]
+    pub resume_token: Option<String>,
+    // Only sent by an operator that wants the video
+    #[serde(default)]
+    pub video_offer: Option<VideoOffer>,
+    // Only sent by the vehicle, once it accepted the offer
+    #[serde(default)]
+    pub video: Option<VideoTransport>,
+}
+
+/// What both sides agreed on for the rest of the connection
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Negotiated {
+    pub capabilities: Vec<Capability>,
+    pub wire_format: WireFormat,
+    // Left for the vehicle to fill in, from the operator's offer
+    pub video: Option<VideoTransport>,
+}
+
+impl Hello {
+    pub fn new(capabilities: Vec<Capability>, wire_formats: Vec<WireFormat>) -> Self {
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            build: BuildInfo::current(),
+            capabilities,
+            wire_formats,
+            resume_token: None,
+            video_offer: None,
+            video: None,
+        }
+    }
+
+    pub fn with_resume_token(mut self, resume_token: Option<String>) -> Self {
+        self.resume_token = resume_token;
+        self
+    }
+
+    pub fn with_video_offer(mut self, video_offer: Option<VideoOffer>) -> Self {
+        self.video_offer = video_offer;
+        self
+    }
+
+    /// The vehicle's answer to the operator's hello
+    pub fn reply(negotiated: &Negotiated) -> Self {
+        Self {
+            video: negotiated.video.clone(),
+            ..Self::new(
+                negotiated.capabilities.clone(),
+                vec![negotiated.wire_format],
+            )
+        }
+    }
+
+    /// Returns the reason the peer can't be talked to, if it can't
+    pub fn negotiate(&self, peer: &Hello) -> Result<Negotiated, String> {
+        if peer.protocol_version != self.protocol_version {
+            return Err(format!(
+                "Protocol version {} is not supported, expected {}",
+                peer.protocol_version, self.protocol_version
+            ));
+        }
+
+        // The peer's preference wins, the operator is the one choosing
+        let wire_format = peer
+            .wire_formats
+            .iter()
+            .find(|format| self.wire_formats.contains(format))
+            .copied()
+            .ok_or_else(|| {
+                format!(
+                    "None of the wire formats {:?} is supported",
+                    peer.wire_formats
+                )
+            })?;
+
+        // Only the vehicle's answer describes a stream, which must be the one offered
+        if let Some(video) = &peer.video {
+            match &self.video_offer {
+                Some(offer) if offer.port == video.port && offer.codecs.contains(&video.codec) => {}
+                _ => return Err(format!("Video {video:?} was not offered")),
+            }
+        }
+
+        Ok(Negotiated {
+            capabilities: self
+                .capabilities
+                .iter()
+                .filter(|capability| peer.capabilities.contains(capability))
+                .copied()
+                .collect(),
+            wire_format,
+            video: peer.video.clone(),
+        })
+    }
+
+    pub fn read_from_json(msg: &str) -> Result<Self, GoliathSerdeError> {
+        check_message_size(msg.as_bytes())?;
+        let hello = serde_json::from_str(msg)?;
+        Ok(hello)
+    }
+
+    pub fn into_json(self) -> Result<String, GoliathSerdeError> {
+        let data = serde_json::to_string(&self)?;
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAYLOAD_TYPE: u8 = 96;
+    const SSRC: u32 = 0x1234_5678;
+
+    fn offer(port: u16) -> VideoOffer {
+        VideoOffer {
+            port,
+            codecs: vec![VideoCodec::H264],
+        }
+    }
+
+    fn operator_hello(video_offer: Option<VideoOffer>) -> Hello {
+        Hello::new(vec![Capability::VideoH264], vec![WireFormat::Bitcode])
+            .with_video_offer(video_offer)
+    }
+
+    /// What the vehicle answers, with its own video transport
+    fn vehicle_reply(video: Option<VideoTransport>) -> Hello {
+        Hello::reply(&Negotiated {
+            capabilities: vec![Capability::VideoH264],
+            wire_format: WireFormat::Bitcode,
+            video,
+        })
+    }
+
+    #[test]
+    fn offer_is_answered_with_the_offered_port() {
+        let transport = offer(5600)
+            .accept(&[VideoCodec::H264], PAYLOAD_TYPE, SSRC)
+            .unwrap();
+        assert_eq!(
+            transport,
+            VideoTransport {
+                port: 5600,
+                codec: VideoCodec::H264,
+                payload_type: PAYLOAD_TYPE,
+                ssrc: SSRC,
+            }
+        );
+    }
+
+    #[test]
+    fn offer_without_a_common_codec_is_refused() {
+        assert!(offer(5600).accept(&[], PAYLOAD_TYPE, SSRC).is_err());
+        let no_codecs = VideoOffer {
+            port: 5600,
+            codecs: Vec::new(),
+        };
+        assert!(
+            no_codecs
+                .accept(&[VideoCodec::H264], PAYLOAD_TYPE, SSRC)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn offer_keeps_the_operator_preference() {
+        // A single codec exists for now, the order of the operator's list still comes first
+        let offer = VideoOffer {
+            port: 5600,
+            codecs: vec![VideoCodec::H264, VideoCodec::H264],
+        };
+        let transport = offer
+            .accept(&[VideoCodec::H264], PAYLOAD_TYPE, SSRC)
+            .unwrap();
+        assert_eq!(transport.codec, offer.codecs[0]);
+    }
+
+    #[test]
+    fn operator_accepts_the_offered_video() {
+        let operator = operator_hello(Some(offer(5600)));
+        let transport = offer(5600)
+            .accept(&[VideoCodec::H264], PAYLOAD_TYPE, SSRC)
+            .unwrap();
+        let negotiated = operator
+            .negotiate(&vehicle_reply(Some(transport.clone())))
+            .unwrap();
+        assert_eq!(negotiated.video, Some(transport));
+    }
+
+    #[test]
+    fn operator_rejects_video_it_did_not_offer() {
+        let transport = VideoTransport {
+            port: 5600,
+            codec: VideoCodec::H264,
+            payload_type: PAYLOAD_TYPE,
+            ssrc: SSRC,
+        };
+
+        let other_port = VideoTransport {
+            port: 5601,
+            ..transport.clone()
+        };
+        assert!(
+            operator_hello(Some(offer(5600)))
+                .negotiate(&vehicle_reply(Some(other_port)))
+                .is_err()
+        );
+
+        let other_codec = VideoOffer {
+            port: 5600,
+            codecs: Vec::new(),
+        };
+        assert!(
+            operator_hello(Some(other_codec))
+                .negotiate(&vehicle_reply(Some(transport.clone())))
+                .is_err()
+        );
+
+        assert!(
+            operator_hello(None)
+                .negotiate(&vehicle_reply(Some(transport)))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn operator_drives_without_video_if_none_is_sent() {
+        let negotiated = operator_hello(Some(offer(5600)))
+            .negotiate(&vehicle_reply(None))
+            .unwrap();
+        assert_eq!(negotiated.video, None);
+    }
+
+    #[test]
+    fn resumed_session_echoes_the_first_transport() {
+        let first = offer(5600)
+            .accept(&[VideoCodec::H264], PAYLOAD_TYPE, SSRC)
+            .unwrap();
+        let vehicle = Hello::new(vec![Capability::VideoH264], vec![WireFormat::Bitcode]);
+        let mut parked = vehicle
+            .negotiate(&operator_hello(Some(offer(5600))))
+            .unwrap();
+        parked.video = Some(first.clone());
+
+        // The vehicle answers from the parked session, whatever it would pick now
+        let reply = Hello::reply(&parked).with_resume_token(Some("token".to_string()));
+        let operator =
+            operator_hello(Some(offer(5600))).with_resume_token(Some("token".to_string()));
+        let negotiated = operator.negotiate(&reply).unwrap();
+        assert_eq!(negotiated.video, Some(first));
+        assert_eq!(reply.resume_token, operator.resume_token);
+    }
+}
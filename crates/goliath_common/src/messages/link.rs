@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+
+/// Rolling view of the link, from the side that sent the pings
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LinkReport {
+    pub rtt_us: u64,    // Mean over the window
+    pub jitter_us: u64, // Mean difference between consecutive round trips
+    // Peer's clock minus the local clock, taken from the fastest round trip
+    pub clock_offset_us: i64,
+    pub samples: u32,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct LinkSample {
+    rtt_us: i64,
+    offset_us: i64,
+}
+
+/// Keeps the last few ping/pong exchanges, NTP style
+pub struct LinkStats {
+    samples: VecDeque<LinkSample>,
+    window: usize,
+}
+
+impl LinkStats {
+    pub fn new(window: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(window),
+            window,
+        }
+    }
+
+    /// Ping sent and pong received are on the local clock, ping received and pong sent on the
+    /// peer's. Timestamps too far apart to compute with, which the peer made up, are dropped
+    pub fn record(
+        &mut self,
+        ping_sent_us: u64,
+        ping_received_us: u64,
+        pong_sent_us: u64,
+        pong_received_us: u64,
+    ) {
+        let Some(sample) = Self::sample(
+            ping_sent_us,
+            ping_received_us,
+            pong_sent_us,
+            pong_received_us,
+        ) else {
+            log::debug!(
+                "Dropping link sample with timestamps {ping_sent_us}, {ping_received_us}, \
+                 {pong_sent_us} and {pong_received_us}"
+            );
+            return;
+        };
+
+        if self.samples.len() == self.window {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    fn sample(t0: u64, t1: u64, t2: u64, t3: u64) -> Option<LinkSample> {
+        let [t0, t1, t2, t3] = [
+            i64::try_from(t0).ok()?,
+            i64::try_from(t1).ok()?,
+            i64::try_from(t2).ok()?,
+            i64::try_from(t3).ok()?,
+        ];
+
+        // Time spent on the peer doesn't count towards the round trip
+        let round_trip_us = t3.checked_sub(t0)?;
+        let on_peer_us = t2.checked_sub(t1)?;
+        Some(LinkSample {
+            rtt_us: round_trip_us.checked_sub(on_peer_us)?.max(0),
+            offset_us: t1.checked_sub(t0)?.checked_add(t2.checked_sub(t3)?)? / 2,
+        })
+    }
+
+    pub fn report(&self) -> Option<LinkReport> {
+        let count = self.samples.len() as i128;
+        let fastest = self.samples.iter().min_by_key(|sample| sample.rtt_us)?;
+
+        // Round trips are never negative, so neither their sum nor their differences overflow
+        let rtt_us = self
+            .samples
+            .iter()
+            .map(|sample| i128::from(sample.rtt_us))
+            .sum::<i128>()
+            / count;
+        let jitter_us = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .map(|(previous, sample)| i128::from((sample.rtt_us - previous.rtt_us).abs()))
+            .sum::<i128>()
+            / (count - 1).max(1);
+
+        Some(LinkReport {
+            rtt_us: rtt_us as u64,
+            jitter_us: jitter_us as u64,
+            clock_offset_us: fastest.offset_us,
+            samples: count as u32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_excludes_the_time_on_the_peer() {
+        let mut stats = LinkStats::new(4);
+        // 250us each way, 300us on the peer, whose clock is 3750us ahead
+        stats.record(1_000, 5_000, 5_300, 1_800);
+
+        let report = stats.report().unwrap();
+        assert_eq!(report.rtt_us, 500);
+        assert_eq!(report.clock_offset_us, 3_750);
+        assert_eq!(report.jitter_us, 0);
+        assert_eq!(report.samples, 1);
+    }
+
+    #[test]
+    fn peer_behind_gives_a_negative_offset() {
+        let mut stats = LinkStats::new(4);
+        stats.record(10_000, 2_250, 2_300, 10_550);
+
+        let report = stats.report().unwrap();
+        assert_eq!(report.rtt_us, 500);
+        assert_eq!(report.clock_offset_us, -8_000);
+    }
+
+    #[test]
+    fn round_trip_is_clamped_at_zero() {
+        let mut stats = LinkStats::new(4);
+        // The peer claims to have spent longer on the ping than the whole round trip
+        stats.record(0, 100, 1_000, 500);
+        assert_eq!(stats.report().unwrap().rtt_us, 0);
+    }
+
+    #[test]
+    fn overflowing_samples_are_dropped() {
+        let mut stats = LinkStats::new(4);
+        stats.record(0, u64::MAX, u64::MAX, 0);
+        stats.record(0, i64::MAX as u64, i64::MAX as u64, 0);
+        stats.record(u64::MAX, 0, 0, u64::MAX);
+        assert_eq!(stats.report(), None);
+
+        stats.record(1_000, 5_000, 5_300, 1_800);
+        assert_eq!(stats.report().unwrap().samples, 1);
+    }
+
+    #[test]
+    fn window_keeps_the_latest_samples() {
+        let mut stats = LinkStats::new(4);
+        for idx in 1..=6 {
+            // Round trips of 100us to 600us, no time on the peer
+            stats.record(0, 0, 0, idx * 100);
+        }
+
+        let report = stats.report().unwrap();
+        assert_eq!(report.samples, 4);
+        assert_eq!(report.rtt_us, 450);
+        assert_eq!(report.jitter_us, 100);
+        // From the fastest kept sample, the 300us one
+        assert_eq!(report.clock_offset_us, -150);
+    }
+}
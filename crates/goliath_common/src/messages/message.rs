@@ -1,3 +1,231 @@
-#[derive(serde::Serialize, serde::Deserialize)]
-#[serde(tag = "type", content = "data", rename_all = "snake_case")]
-pub enum GoliathMessage {}
+use crate::{GoliathCommand, GoliathReport};
+use std::time::{Duration, Instant};
+
+// How fast the baseline may follow the clocks apart, well above the 100ppm quartz clocks drift
+const MAX_CLOCK_DRIFT: f64 = 0.001;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GoliathPayload {
+    Command(GoliathCommand),
+    Report(GoliathReport),
+    // Answers a message that requested it, `applied` is false if it was rejected or discarded
+    Ack {
+        seq: u64,
+        applied: bool,
+    },
+    // Measures the link, answered right away with a pong
+    Ping,
+    // The pong's own envelope timestamp is when it was sent
+    Pong {
+        ping_timestamp_us: u64,
+        ping_received_us: u64,
+    },
+}
+
+/// Every message on the control websocket is wrapped in an envelope
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GoliathMessage {
+    pub seq: u64,          // Increasing per sender, starting from 0 on each connection
+    pub timestamp_us: u64, // Sender's monotonic clock, not comparable to the receiver's clock
+    pub ack_requested: bool,
+    pub payload: GoliathPayload,
+}
+
+/// Monotonic clock of one side of a connection, all of that side's timestamps come from it
+#[derive(Copy, Clone, Debug)]
+pub struct SessionClock {
+    started: Instant,
+}
+
+impl SessionClock {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+        }
+    }
+
+    pub fn now_us(&self) -> u64 {
+        self.started.elapsed().as_micros() as u64
+    }
+}
+
+impl Default for SessionClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Numbers and timestamps the outgoing messages of a connection
+pub struct MessageStamper {
+    clock: SessionClock,
+    next_seq: u64,
+}
+
+impl MessageStamper {
+    pub fn new(clock: SessionClock) -> Self {
+        Self { clock, next_seq: 0 }
+    }
+
+    pub fn stamp(&mut self, payload: GoliathPayload, ack_requested: bool) -> GoliathMessage {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        GoliathMessage {
+            seq,
+            timestamp_us: self.clock.now_us(),
+            ack_requested,
+            payload,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Delivery {
+    Fresh,
+    OutOfOrder, // An equal or later sequence number was already received
+    Stale,      // Took longer than the max age to arrive
+}
+
+/// Classifies the incoming messages of a connection.
+///
+/// The clocks of the two sides are unrelated, so the age of a message is measured against the
+/// fastest message seen so far: its delay is taken as the baseline, and anything arriving much
+/// later than that is considered stale. The baseline rises slowly over time, so the clocks
+/// drifting apart doesn't end up making every message stale
+pub struct MessageFilter {
+    clock: SessionClock,
+    max_age: Duration,
+    last_seq: Option<u64>,
+    // Lowest local receive time minus sender timestamp, and the local time it was seen at
+    baseline: Option<(i128, u64)>,
+}
+
+impl MessageFilter {
+    pub fn new(clock: SessionClock, max_age: Duration) -> Self {
+        Self {
+            clock,
+            max_age,
+            last_seq: None,
+            baseline: None,
+        }
+    }
+
+    pub fn check(&mut self, msg: &GoliathMessage) -> Delivery {
+        self.check_at(msg, self.clock.now_us())
+    }
+
+    fn check_at(&mut self, msg: &GoliathMessage, now_us: u64) -> Delivery {
+        // Wide enough for any timestamp the peer sends
+        let offset_us = i128::from(now_us) - i128::from(msg.timestamp_us);
+        // Beyond what either clock can reach, so it must not move the baseline
+        let absurd = i64::try_from(offset_us).is_err();
+        let baseline_us = match self.baseline {
+            _ if absurd => None,
+            Some((min_offset_us, seen_us)) => {
+                let drift_us = (now_us.saturating_sub(seen_us) as f64 * MAX_CLOCK_DRIFT) as i128;
+                Some(min_offset_us + drift_us)
+            }
+            None => Some(offset_us),
+        };
+        let baseline_us = match baseline_us {
+            Some(baseline_us) if offset_us <= baseline_us => {
+                self.baseline = Some((offset_us, now_us));
+                Some(offset_us)
+            }
+            baseline_us => baseline_us,
+        };
+
+        if self.last_seq.is_some_and(|last_seq| msg.seq <= last_seq) {
+            return Delivery::OutOfOrder;
+        }
+        self.last_seq = Some(msg.seq);
+
+        let Some(baseline_us) = baseline_us else {
+            return Delivery::Stale;
+        };
+        if (offset_us - baseline_us) as u128 > self.max_age.as_micros() {
+            Delivery::Stale
+        } else {
+            Delivery::Fresh
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_AGE: Duration = Duration::from_millis(500);
+    const DELAY_US: u64 = 10_000;
+
+    fn message(seq: u64, timestamp_us: u64) -> GoliathMessage {
+        GoliathMessage {
+            seq,
+            timestamp_us,
+            ack_requested: false,
+            payload: GoliathPayload::Ping,
+        }
+    }
+
+    #[test]
+    fn late_and_reordered_messages_are_flagged() {
+        let mut filter = MessageFilter::new(SessionClock::new(), MAX_AGE);
+        assert_eq!(filter.check_at(&message(0, 0), DELAY_US), Delivery::Fresh);
+        assert_eq!(
+            filter.check_at(&message(1, 1_000), 1_000 + DELAY_US + 600_000),
+            Delivery::Stale
+        );
+        assert_eq!(
+            filter.check_at(&message(1, 2_000), 2_000 + DELAY_US),
+            Delivery::OutOfOrder
+        );
+        assert_eq!(
+            filter.check_at(&message(2, 3_000), 3_000 + DELAY_US),
+            Delivery::Fresh
+        );
+    }
+
+    #[test]
+    fn absurd_timestamps_are_stale() {
+        let mut filter = MessageFilter::new(SessionClock::new(), MAX_AGE);
+        assert_eq!(
+            filter.check_at(&message(0, u64::MAX), DELAY_US),
+            Delivery::Stale
+        );
+
+        // The baseline is left alone
+        assert_eq!(filter.check_at(&message(2, 0), DELAY_US), Delivery::Fresh);
+        assert_eq!(
+            filter.check_at(&message(3, u64::MAX), 1_000 + DELAY_US),
+            Delivery::Stale
+        );
+        assert_eq!(
+            filter.check_at(&message(4, 2_000), 2_000 + DELAY_US),
+            Delivery::Fresh
+        );
+    }
+
+    #[test]
+    fn clock_drift_does_not_make_messages_stale() {
+        let mut filter = MessageFilter::new(SessionClock::new(), MAX_AGE);
+        // The sender's clock runs 200ppm slow, 720ms behind after an hour
+        let mut now_us = 0;
+        for seq in 0..3_600 {
+            now_us = seq * 1_000_000 + DELAY_US;
+            let timestamp_us = seq * 999_800;
+            assert_eq!(
+                filter.check_at(&message(seq, timestamp_us), now_us),
+                Delivery::Fresh,
+                "message #{seq}"
+            );
+        }
+
+        // A message held up on the way is still noticed
+        let timestamp_us = 3_600 * 999_800;
+        now_us += 1_000_000 + 600_000;
+        assert_eq!(
+            filter.check_at(&message(3_600, timestamp_us), now_us),
+            Delivery::Stale
+        );
+    }
+}
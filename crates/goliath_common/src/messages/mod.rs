@@ -1,9 +1,28 @@
+mod auth;
+mod codec;
 mod commands;
 mod error;
+mod handshake;
+mod link;
 mod message;
 mod reports;
 
-pub use commands::{GoliathCommand, MotorCommand};
+pub use auth::{AuthMessage, AuthProof, Role};
+pub use codec::{
+    BitcodeCodec, EncodedMessage, GoliathCodec, JsonCodec, MAX_MESSAGE_SIZE, WireFormat,
+    codec_for_format,
+};
+pub use commands::{DriveMode, GoliathCommand, MotorCommand, PowerCurve, Track, TrackCalibration};
 pub use error::GoliathSerdeError;
-pub use message::GoliathMessage;
-pub use reports::GoliathReport;
+pub use handshake::{
+    BuildInfo, Capability, Hello, Negotiated, PROTOCOL_VERSION, VideoCodec, VideoOffer,
+    VideoTransport,
+};
+pub use link::{LinkReport, LinkStats};
+pub use message::{
+    Delivery, GoliathMessage, GoliathPayload, MessageFilter, MessageStamper, SessionClock,
+};
+pub use reports::{
+    CommandRejection, GoliathReport, MotorReport, MotorsStatus, TelemetryReport, TrackTelemetry,
+    VideoState,
+};
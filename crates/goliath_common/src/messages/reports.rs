@@ -1,17 +1,75 @@
-use crate::GoliathSerdeError;
-use bytes::Bytes;
+use crate::{Capability, LinkReport, Role};
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-pub enum GoliathReport {}
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MotorReport {
+    // No command was received for the timeout, the tracks were stopped
+    WatchdogTriggered { timeout_ms: u64 },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TrackTelemetry {
+    pub power: f32, // Between 0.0 and 1.0
+    pub forward: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum MotorsStatus {
+    Running,
+    Stopped,
+}
 
-impl GoliathReport {
-    pub fn read_from_bytes(msg_bytes: &[u8]) -> Result<Self, GoliathSerdeError> {
-        let cmd = bitcode::deserialize(msg_bytes)?;
-        Ok(cmd)
-    }
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum VideoState {
+    Stopped,
+    Ready,
+    Paused,
+    Playing,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TelemetryReport {
+    pub left_track: TrackTelemetry,
+    pub right_track: TrackTelemetry,
+    pub turret_angle: f32, // Degrees, 0 is facing forward
+    pub uptime_ms: u64,    // Since the session started
+    pub motors: MotorsStatus,
+    pub video: VideoState,
+    // None if the vehicle could not read them
+    pub cpu_temperature: Option<f32>, // Celsius
+    pub cpu_load: Option<f32>,        // Load average over the last minute
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum CommandRejection {
+    NotFinite,
+    OutOfRange { min: f32, max: f32 },
+    InvalidCalibration(String),
+    EmergencyStopEngaged,
+    NotNegotiated(Capability),
+    NotPermitted(Role),
+    NoDrivingLease,
+    // Another connection holds the driving lease, `holder` describes it
+    LeaseHeld { holder: String },
+}
 
-    pub fn into_bytes(self) -> Result<Bytes, GoliathSerdeError> {
-        let data = bitcode::serialize(&self)?;
-        Ok(Bytes::from_owner(data))
-    }
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub enum GoliathReport {
+    Motor(MotorReport),
+    // The command was not applied, `command` is its debug representation
+    CommandRejected {
+        command: String,
+        reason: CommandRejection,
+    },
+    // Sent whenever the latched emergency stop changes, and once on connection
+    EmergencyStop {
+        engaged: bool,
+    },
+    Telemetry(TelemetryReport),
+    // The link as measured by the vehicle's own pings
+    LinkHealth(LinkReport),
+    // Sent whenever the lease changes hands, and once on connection
+    DrivingLease {
+        holder: Option<String>,
+        yours: bool,
+    },
 }
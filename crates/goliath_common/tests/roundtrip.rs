@@ -0,0 +1,338 @@
+use goliath_common::{
+    AuthMessage, AuthProof, BitcodeCodec, BuildInfo, Capability, CommandRejection, DriveMode,
+    EncodedMessage, GoliathCodec, GoliathCommand, GoliathMessage, GoliathPayload, GoliathReport,
+    GoliathSerdeError, Hello, JsonCodec, LinkReport, MAX_MESSAGE_SIZE, MotorCommand, MotorReport,
+    MotorsStatus, PowerCurve, Role, TelemetryReport, Track, TrackCalibration, TrackTelemetry,
+    VideoCodec, VideoOffer, VideoState, VideoTransport, WireFormat,
+};
+use proptest::prelude::*;
+
+// JSON has no representation for non-finite floats, the vehicle rejects them anyway
+fn finite() -> impl Strategy<Value = f32> {
+    -1.0e6f32..1.0e6f32
+}
+
+fn drive_mode() -> impl Strategy<Value = DriveMode> {
+    prop_oneof![
+        Just(DriveMode::Arcade),
+        Just(DriveMode::Tank),
+        Just(DriveMode::Pivot)
+    ]
+}
+
+fn track() -> impl Strategy<Value = Track> {
+    prop_oneof![Just(Track::Left), Just(Track::Right)]
+}
+
+fn capability() -> impl Strategy<Value = Capability> {
+    prop_oneof![
+        Just(Capability::Turret),
+        Just(Capability::Telemetry),
+        Just(Capability::VideoH264)
+    ]
+}
+
+fn role() -> impl Strategy<Value = Role> {
+    prop_oneof![Just(Role::Observer), Just(Role::Driver), Just(Role::Safety)]
+}
+
+fn wire_format() -> impl Strategy<Value = WireFormat> {
+    prop_oneof![Just(WireFormat::Bitcode), Just(WireFormat::Json)]
+}
+
+fn power_curve() -> impl Strategy<Value = PowerCurve> {
+    prop_oneof![
+        Just(PowerCurve::Linear),
+        finite().prop_map(PowerCurve::Exponential),
+        prop::collection::vec(finite(), 0..16).prop_map(PowerCurve::Lookup),
+    ]
+}
+
+fn track_calibration() -> impl Strategy<Value = TrackCalibration> {
+    (finite(), finite(), power_curve()).prop_map(|(min_power, trim, curve)| TrackCalibration {
+        min_power,
+        trim,
+        curve,
+    })
+}
+
+// Heartbeat and End never leave the vehicle, they are not serialized
+fn motor_command() -> impl Strategy<Value = MotorCommand> {
+    prop_oneof![
+        finite().prop_map(MotorCommand::Thrust),
+        finite().prop_map(MotorCommand::Steer),
+        finite().prop_map(MotorCommand::LeftTrack),
+        finite().prop_map(MotorCommand::RightTrack),
+        (finite(), finite()).prop_map(|(thrust, steer)| MotorCommand::Drive { thrust, steer }),
+        (finite(), finite()).prop_map(|(left, right)| MotorCommand::Tank { left, right }),
+        drive_mode().prop_map(MotorCommand::SetDriveMode),
+        (track(), track_calibration())
+            .prop_map(|(track, calibration)| MotorCommand::Calibrate { track, calibration }),
+        finite().prop_map(MotorCommand::TurretAngle),
+        finite().prop_map(MotorCommand::TurretRate),
+        Just(MotorCommand::TurretHome),
+    ]
+}
+
+fn command() -> impl Strategy<Value = GoliathCommand> {
+    prop_oneof![
+        motor_command().prop_map(GoliathCommand::Motor),
+        Just(GoliathCommand::Heartbeat),
+        Just(GoliathCommand::EmergencyStop),
+        Just(GoliathCommand::ClearEmergencyStop),
+        Just(GoliathCommand::TakeControl),
+        Just(GoliathCommand::ReleaseControl),
+    ]
+}
+
+fn track_telemetry() -> impl Strategy<Value = TrackTelemetry> {
+    (finite(), any::<bool>()).prop_map(|(power, forward)| TrackTelemetry { power, forward })
+}
+
+fn telemetry_report() -> impl Strategy<Value = TelemetryReport> {
+    (
+        track_telemetry(),
+        track_telemetry(),
+        finite(),
+        any::<u64>(),
+        prop_oneof![Just(MotorsStatus::Running), Just(MotorsStatus::Stopped)],
+        prop_oneof![
+            Just(VideoState::Stopped),
+            Just(VideoState::Ready),
+            Just(VideoState::Paused),
+            Just(VideoState::Playing)
+        ],
+        proptest::option::of(finite()),
+        proptest::option::of(finite()),
+    )
+        .prop_map(
+            |(left_track, right_track, turret_angle, uptime_ms, motors, video, temp, load)| {
+                TelemetryReport {
+                    left_track,
+                    right_track,
+                    turret_angle,
+                    uptime_ms,
+                    motors,
+                    video,
+                    cpu_temperature: temp,
+                    cpu_load: load,
+                }
+            },
+        )
+}
+
+fn link_report() -> impl Strategy<Value = LinkReport> {
+    (any::<u64>(), any::<u64>(), any::<i64>(), any::<u32>()).prop_map(
+        |(rtt_us, jitter_us, clock_offset_us, samples)| LinkReport {
+            rtt_us,
+            jitter_us,
+            clock_offset_us,
+            samples,
+        },
+    )
+}
+
+fn command_rejection() -> impl Strategy<Value = CommandRejection> {
+    prop_oneof![
+        Just(CommandRejection::NotFinite),
+        (finite(), finite()).prop_map(|(min, max)| CommandRejection::OutOfRange { min, max }),
+        ".*".prop_map(CommandRejection::InvalidCalibration),
+        Just(CommandRejection::EmergencyStopEngaged),
+        capability().prop_map(CommandRejection::NotNegotiated),
+        role().prop_map(CommandRejection::NotPermitted),
+        Just(CommandRejection::NoDrivingLease),
+        ".*".prop_map(|holder| CommandRejection::LeaseHeld { holder }),
+    ]
+}
+
+fn report() -> impl Strategy<Value = GoliathReport> {
+    prop_oneof![
+        any::<u64>().prop_map(
+            |timeout_ms| GoliathReport::Motor(MotorReport::WatchdogTriggered { timeout_ms })
+        ),
+        (".*", command_rejection())
+            .prop_map(|(command, reason)| GoliathReport::CommandRejected { command, reason }),
+        any::<bool>().prop_map(|engaged| GoliathReport::EmergencyStop { engaged }),
+        telemetry_report().prop_map(GoliathReport::Telemetry),
+        link_report().prop_map(GoliathReport::LinkHealth),
+        (proptest::option::of(".*"), any::<bool>())
+            .prop_map(|(holder, yours)| GoliathReport::DrivingLease { holder, yours }),
+    ]
+}
+
+fn payload() -> impl Strategy<Value = GoliathPayload> {
+    prop_oneof![
+        command().prop_map(GoliathPayload::Command),
+        report().prop_map(GoliathPayload::Report),
+        (any::<u64>(), any::<bool>())
+            .prop_map(|(seq, applied)| GoliathPayload::Ack { seq, applied }),
+        Just(GoliathPayload::Ping),
+        (any::<u64>(), any::<u64>()).prop_map(|(ping_timestamp_us, ping_received_us)| {
+            GoliathPayload::Pong {
+                ping_timestamp_us,
+                ping_received_us,
+            }
+        }),
+    ]
+}
+
+fn message() -> impl Strategy<Value = GoliathMessage> {
+    (any::<u64>(), any::<u64>(), any::<bool>(), payload()).prop_map(
+        |(seq, timestamp_us, ack_requested, payload)| GoliathMessage {
+            seq,
+            timestamp_us,
+            ack_requested,
+            payload,
+        },
+    )
+}
+
+fn video_offer() -> impl Strategy<Value = VideoOffer> {
+    (
+        any::<u16>(),
+        prop::collection::vec(Just(VideoCodec::H264), 0..2),
+    )
+        .prop_map(|(port, codecs)| VideoOffer { port, codecs })
+}
+
+fn video_transport() -> impl Strategy<Value = VideoTransport> {
+    (any::<u16>(), any::<u8>(), any::<u32>()).prop_map(|(port, payload_type, ssrc)| {
+        VideoTransport {
+            port,
+            codec: VideoCodec::H264,
+            payload_type,
+            ssrc,
+        }
+    })
+}
+
+fn hello() -> impl Strategy<Value = Hello> {
+    (
+        (any::<u32>(), ".*", proptest::option::of(".*")),
+        prop::collection::vec(capability(), 0..4),
+        prop::collection::vec(wire_format(), 0..3),
+        proptest::option::of(".*"),
+        proptest::option::of(video_offer()),
+        proptest::option::of(video_transport()),
+    )
+        .prop_map(
+            |(
+                (protocol_version, version, commit),
+                capabilities,
+                wire_formats,
+                resume_token,
+                video_offer,
+                video,
+            )| Hello {
+                protocol_version,
+                build: BuildInfo { version, commit },
+                capabilities,
+                wire_formats,
+                resume_token,
+                video_offer,
+                video,
+            },
+        )
+}
+
+fn auth_message() -> impl Strategy<Value = AuthMessage> {
+    let bytes = || prop::collection::vec(any::<u8>(), 0..64);
+    let proof = prop_oneof![
+        Just(AuthProof::None),
+        ".*".prop_map(AuthProof::Token),
+        (bytes(), bytes()).prop_map(|(public_key, signature)| AuthProof::Ed25519 {
+            public_key,
+            signature
+        }),
+    ];
+
+    prop_oneof![
+        bytes().prop_map(|nonce| AuthMessage::Challenge { nonce }),
+        (role(), proof).prop_map(|(role, proof)| AuthMessage::Request { role, proof }),
+        (role(), ".*").prop_map(|(role, resume_token)| AuthMessage::Granted { role, resume_token }),
+    ]
+}
+
+fn encoded_bytes(encoded: EncodedMessage) -> Vec<u8> {
+    match encoded {
+        EncodedMessage::Binary(data) => data.to_vec(),
+        EncodedMessage::Text(text) => text.into_bytes(),
+    }
+}
+
+fn assert_too_large(result: Result<impl std::fmt::Debug, GoliathSerdeError>) {
+    assert!(
+        matches!(result, Err(GoliathSerdeError::MessageTooLarge { .. })),
+        "expected MessageTooLarge, got {result:?}"
+    );
+}
+
+proptest! {
+    #[test]
+    fn bitcode_roundtrip(msg in message()) {
+        let data = encoded_bytes(BitcodeCodec.encode(&msg).unwrap());
+        prop_assert_eq!(BitcodeCodec.decode(&data).unwrap(), msg);
+    }
+
+    #[test]
+    fn json_roundtrip(msg in message()) {
+        let data = encoded_bytes(JsonCodec.encode(&msg).unwrap());
+        prop_assert_eq!(JsonCodec.decode(&data).unwrap(), msg);
+    }
+
+    #[test]
+    fn hello_roundtrip(hello in hello()) {
+        let json = hello.clone().into_json().unwrap();
+        prop_assert_eq!(Hello::read_from_json(&json).unwrap(), hello);
+    }
+
+    #[test]
+    fn auth_message_roundtrip(auth_msg in auth_message()) {
+        let json = auth_msg.clone().into_json().unwrap();
+        prop_assert_eq!(AuthMessage::read_from_json(&json).unwrap(), auth_msg);
+    }
+
+    // Only checks that decoding returns instead of panicking, almost all of it is invalid
+    #[test]
+    fn arbitrary_bytes_do_not_panic(data in prop::collection::vec(any::<u8>(), 0..256)) {
+        let _ = BitcodeCodec.decode(&data);
+        let _ = JsonCodec.decode(&data);
+        let _ = Hello::read_from_json(&String::from_utf8_lossy(&data));
+        let _ = AuthMessage::read_from_json(&String::from_utf8_lossy(&data));
+    }
+
+    // Valid messages cut short or with flipped bytes are the likeliest corruption
+    #[test]
+    fn corrupted_bitcode_does_not_panic(
+        msg in message(),
+        cut in any::<prop::sample::Index>(),
+        flip in any::<(prop::sample::Index, u8)>(),
+    ) {
+        let mut data = encoded_bytes(BitcodeCodec.encode(&msg).unwrap());
+        let _ = BitcodeCodec.decode(&data[..cut.index(data.len() + 1)]);
+
+        let (position, mask) = flip;
+        let position = position.index(data.len());
+        data[position] ^= mask;
+        let _ = BitcodeCodec.decode(&data);
+    }
+}
+
+#[test]
+fn oversized_input_is_rejected() {
+    let data = vec![b' '; MAX_MESSAGE_SIZE + 1];
+    assert_too_large(BitcodeCodec.decode(&data));
+    assert_too_large(JsonCodec.decode(&data));
+    let text = String::from_utf8(data).unwrap();
+    assert_too_large(Hello::read_from_json(&text));
+    assert_too_large(AuthMessage::read_from_json(&text));
+}
+
+#[test]
+fn input_at_the_limit_reaches_the_decoder() {
+    let data = vec![b' '; MAX_MESSAGE_SIZE];
+    assert!(matches!(
+        JsonCodec.decode(&data),
+        Err(GoliathSerdeError::SerdeJsonError(_))
+    ));
+}
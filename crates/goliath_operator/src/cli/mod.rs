@@ -0,0 +1,173 @@
+use crate::config::{
+    DEFAULT_CONFIG_PATH, OperatorConfig, VehicleProfile, VehicleTarget, VideoSink,
+};
+use crate::error::GoliathOperatorResult;
+use goliath_common::{PowerCurve, Track, TrackCalibration, WireFormat};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Connects to a vehicle, every option overrides its value from the config file
+#[derive(Debug, clap::Parser)]
+#[command(version, about)]
+pub(crate) struct Cli {
+    /// TOML config, the defaults are used if it does not exist
+    #[arg(short, long, env = "GOLIATH_OPERATOR_CONFIG", default_value = DEFAULT_CONFIG_PATH)]
+    config: PathBuf,
+
+    /// Profile of the vehicle in the config, the default one if not given
+    #[arg(short, long, env = "GOLIATH_OPERATOR_VEHICLE", conflicts_with = "host")]
+    vehicle: Option<String>,
+
+    /// Address or name of a vehicle without a profile
+    #[arg(long, env = "GOLIATH_OPERATOR_HOST")]
+    host: Option<String>,
+
+    /// Port of the vehicle's control websocket
+    #[arg(long, env = "GOLIATH_OPERATOR_PORT")]
+    port: Option<u16>,
+
+    /// Encoding of the control messages, JSON is bigger but readable when debugging
+    #[arg(long, env = "GOLIATH_OPERATOR_WIRE_FORMAT", value_enum)]
+    wire_format: Option<WireFormatArg>,
+
+    #[command(subcommand)]
+    pub(crate) command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub(crate) enum Command {
+    /// Drive the vehicle with the controller while watching its video
+    Drive {
+        /// Where the controller sends its state to
+        #[arg(long, env = "GOLIATH_OPERATOR_INPUT")]
+        input: Option<SocketAddr>,
+
+        #[command(flatten)]
+        video: VideoArgs,
+    },
+    /// Watch the video and telemetry as an observer, without driving
+    Watch {
+        #[command(flatten)]
+        video: VideoArgs,
+    },
+    /// Measure the round trip time to the vehicle
+    Ping {
+        /// Number of pings to wait for
+        #[arg(short = 'n', long, default_value_t = 4)]
+        count: u32,
+    },
+    /// Tune the calibration of a track, the vehicle keeps it until it restarts
+    Calibrate {
+        #[command(flatten)]
+        calibration: CalibrationArgs,
+    },
+}
+
+#[derive(Copy, Clone, Debug, clap::ValueEnum)]
+pub(crate) enum WireFormatArg {
+    Bitcode,
+    Json,
+}
+
+#[derive(Copy, Clone, Debug, clap::ValueEnum)]
+pub(crate) enum TrackArg {
+    Left,
+    Right,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct CalibrationArgs {
+    #[arg(value_enum)]
+    track: TrackArg,
+
+    /// Lowest power the motor actually moves at
+    #[arg(long, default_value_t = 0.0)]
+    min_power: f32,
+
+    /// Multiplier evening out the tracks, so the vehicle drives straight
+    #[arg(long, default_value_t = 1.0)]
+    trim: f32,
+
+    /// Exponent of the power curve, which is linear without it or a lookup
+    #[arg(long, conflicts_with = "lookup")]
+    exponent: Option<f32>,
+
+    /// Comma separated output powers for evenly spaced requested powers from 0.0 to 1.0
+    #[arg(long, value_delimiter = ',')]
+    lookup: Option<Vec<f32>>,
+}
+
+impl CalibrationArgs {
+    /// Validated by the vehicle, which knows its limits
+    pub(crate) fn calibration(&self) -> (Track, TrackCalibration) {
+        let track = match self.track {
+            TrackArg::Left => Track::Left,
+            TrackArg::Right => Track::Right,
+        };
+        let curve = match (self.exponent, &self.lookup) {
+            (Some(exponent), _) => PowerCurve::Exponential(exponent),
+            (None, Some(points)) => PowerCurve::Lookup(points.clone()),
+            (None, None) => PowerCurve::Linear,
+        };
+        let calibration = TrackCalibration {
+            min_power: self.min_power,
+            trim: self.trim,
+            curve,
+        };
+        (track, calibration)
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct VideoArgs {
+    /// Port the video is received on, announced to the vehicle
+    #[arg(long, env = "GOLIATH_OPERATOR_VIDEO_PORT")]
+    video_port: Option<u16>,
+
+    #[arg(long, env = "GOLIATH_OPERATOR_VIDEO_SINK", value_enum)]
+    sink: Option<VideoSink>,
+}
+
+impl Cli {
+    /// Loads the config file, applies the overrides and validates the result
+    pub(crate) fn load_config(&self) -> GoliathOperatorResult<(OperatorConfig, VehicleTarget)> {
+        let mut config = OperatorConfig::load(&self.config)?;
+
+        let video = match &self.command {
+            Command::Drive { input, video } => {
+                if let Some(input) = input {
+                    config.input.listen = *input;
+                }
+                Some(video)
+            }
+            Command::Watch { video } => Some(video),
+            Command::Ping { .. } | Command::Calibrate { .. } => None,
+        };
+        if let Some(video) = video {
+            if let Some(port) = video.video_port {
+                config.video.port = port;
+            }
+            if let Some(sink) = video.sink {
+                config.video.sink = sink;
+            }
+        }
+        match self.wire_format {
+            Some(WireFormatArg::Bitcode) => config.wire_format = WireFormat::Bitcode,
+            Some(WireFormatArg::Json) => config.wire_format = WireFormat::Json,
+            None => {}
+        }
+        config.validate()?;
+
+        let mut profile = match &self.host {
+            Some(host) => VehicleProfile::from_host(host.clone()),
+            None => config.profile(self.vehicle.as_deref())?.clone(),
+        };
+        if let Some(port) = self.port {
+            profile.port = port;
+        }
+        profile.validate()?;
+
+        let target = config.target(profile);
+        Ok((config, target))
+    }
+}
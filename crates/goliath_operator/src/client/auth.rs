@@ -0,0 +1,98 @@
+use crate::config::AuthConfig;
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+use futures_util::{SinkExt, StreamExt};
+use goliath_common::{AuthMessage, AuthProof, Role};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use rustls_pki_types::PrivatePkcs8KeyDer;
+use rustls_pki_types::pem::PemObject;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn receive(
+    stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+) -> GoliathOperatorResult<AuthMessage> {
+    match tokio::time::timeout(AUTH_TIMEOUT, stream.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => Ok(AuthMessage::read_from_json(&text)?),
+        Ok(Some(Ok(Message::Close(Some(frame))))) => {
+            Err(GoliathOperatorError::AuthenticationError(format!(
+                "Vehicle rejected the connection: {}",
+                frame.reason
+            )))
+        }
+        Ok(Some(Ok(msg))) => Err(GoliathOperatorError::AuthenticationError(format!(
+            "Unexpected message during authentication: {msg:?}"
+        ))),
+        Ok(Some(Err(err))) => Err(Box::new(err).into()),
+        Ok(None) => Err(GoliathOperatorError::AuthenticationError(
+            "Vehicle disconnected during authentication".to_string(),
+        )),
+        Err(_) => Err(GoliathOperatorError::AuthenticationError(
+            "Timed out waiting for the vehicle".to_string(),
+        )),
+    }
+}
+
+fn prove(config: &AuthConfig, nonce: &[u8]) -> GoliathOperatorResult<AuthProof> {
+    if let Some(token) = &config.token {
+        return Ok(AuthProof::Token(token.clone()));
+    }
+
+    let Some(private_key_path) = &config.private_key_path else {
+        return Ok(AuthProof::None);
+    };
+
+    let pkcs8 = PrivatePkcs8KeyDer::from_pem_file(private_key_path)?;
+    let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8.secret_pkcs8_der())
+        .map_err(|err| GoliathOperatorError::AuthenticationError(err.to_string()))?;
+    let public_key = key_pair.public_key().as_ref().to_vec();
+    log::info!(
+        "Authenticating with public key {}",
+        public_key
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    );
+
+    Ok(AuthProof::Ed25519 {
+        public_key,
+        signature: key_pair
+            .sign(&AuthMessage::signed_challenge(nonce))
+            .as_ref()
+            .to_vec(),
+    })
+}
+
+/// Answers the vehicle's challenge, returning the role it granted and the session's resume token
+pub(crate) async fn authenticate(
+    stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+    config: &AuthConfig,
+) -> GoliathOperatorResult<(Role, String)> {
+    let AuthMessage::Challenge { nonce } = receive(stream).await? else {
+        return Err(GoliathOperatorError::AuthenticationError(
+            "Expected a challenge from the vehicle".to_string(),
+        ));
+    };
+
+    let request = AuthMessage::Request {
+        role: config.role,
+        proof: prove(config, &nonce)?,
+    };
+    stream
+        .send(Message::Text(request.into_json()?.into()))
+        .await
+        .map_err(Box::new)?;
+
+    match receive(stream).await? {
+        AuthMessage::Granted { role, resume_token } => {
+            log::info!("Vehicle granted the {role:?} role");
+            Ok((role, resume_token))
+        }
+        msg => Err(GoliathOperatorError::AuthenticationError(format!(
+            "Expected a grant from the vehicle, got {msg:?}"
+        ))),
+    }
+}
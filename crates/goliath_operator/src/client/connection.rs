@@ -0,0 +1,208 @@
+use crate::client::{GoliathClient, SessionResume};
+use crate::config::{ReconnectConfig, VehicleTarget};
+use crate::error::GoliathOperatorResult;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// The link to the vehicle, as shown to the operator
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum ConnectionState {
+    Connecting { attempt: u32 },
+    Connected,
+    Degraded, // Still connected, but the vehicle went quiet
+    Lost,
+}
+
+impl fmt::Display for ConnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connecting { attempt } => write!(f, "connecting (attempt {attempt})"),
+            Self::Connected => write!(f, "connected"),
+            Self::Degraded => write!(f, "degraded"),
+            Self::Lost => write!(f, "lost"),
+        }
+    }
+}
+
+/// Exponential delays between attempts, jittered so operators don't retry in lockstep
+struct Backoff {
+    initial: Duration,
+    max: Duration,
+    failures: u32,
+    rng: SystemRandom,
+}
+
+impl Backoff {
+    fn new(config: &ReconnectConfig) -> Self {
+        Self {
+            initial: config.initial_delay(),
+            max: config.max_delay(),
+            failures: 0,
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Between half and all of the doubled delay, so retries still slow down
+    fn next_delay(&mut self) -> Duration {
+        let ceiling = self
+            .initial
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(self.max);
+        self.failures = self.failures.saturating_add(1);
+
+        let mut bytes = [0; 4];
+        let fraction = match self.rng.fill(&mut bytes) {
+            Ok(()) => f64::from(u32::from_le_bytes(bytes)) / f64::from(u32::MAX),
+            Err(_) => 1.0,
+        };
+        ceiling / 2 + ceiling.div_f64(2.0).mul_f64(fraction)
+    }
+
+    fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
+
+/// Keeps reaching the vehicle until it answers or the operator shuts down, resuming the
+/// previous session where possible
+pub(crate) struct ConnectionManager {
+    target: VehicleTarget,
+    video_port: Option<u16>, // Where the video is received, if it is wanted
+    resume: Option<SessionResume>, // Kept across reconnections
+    backoff: Backoff,
+    state: Arc<watch::Sender<ConnectionState>>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl ConnectionManager {
+    pub(crate) fn new(
+        target: VehicleTarget,
+        video_port: Option<u16>,
+        reconnect: &ReconnectConfig,
+        shutdown: watch::Receiver<bool>,
+    ) -> Self {
+        let (state, _) = watch::channel(ConnectionState::Connecting { attempt: 1 });
+        Self {
+            target,
+            video_port,
+            resume: None,
+            backoff: Backoff::new(reconnect),
+            state: Arc::new(state),
+            shutdown,
+        }
+    }
+
+    /// For the UI, every transition is sent
+    pub(crate) fn subscribe(&self) -> watch::Receiver<ConnectionState> {
+        self.state.subscribe()
+    }
+
+    /// For the session, which notices the link degrading or getting lost
+    pub(crate) fn state(&self) -> Arc<watch::Sender<ConnectionState>> {
+        Arc::clone(&self.state)
+    }
+
+    /// None if shutting down first. Only failures a retry could fix are retried, a rejection
+    /// or a misconfiguration is returned
+    pub(crate) async fn connect(&mut self) -> GoliathOperatorResult<Option<GoliathClient>> {
+        let mut attempt = 1;
+        loop {
+            if *self.shutdown.borrow() {
+                return Ok(None);
+            }
+
+            self.state
+                .send_replace(ConnectionState::Connecting { attempt });
+            let connecting =
+                GoliathClient::try_new(&self.target, self.video_port, self.resume.as_ref());
+            let result = tokio::select! {
+                result = connecting => result,
+                _ = self.shutdown.changed() => return Ok(None),
+            };
+
+            match result {
+                Ok(client) => {
+                    self.backoff.reset();
+                    self.resume = Some(client.resume().clone());
+                    self.state.send_replace(ConnectionState::Connected);
+                    return Ok(Some(client));
+                }
+                Err(err) if err.is_transient() => {
+                    let delay = self.backoff.next_delay();
+                    log::warn!(
+                        "Failed to connect to {}:{}: {err}, retrying in {delay:?}",
+                        self.target.host,
+                        self.target.port
+                    );
+                    tokio::select! {
+                        _ = tokio::time::sleep(delay) => {}
+                        _ = self.shutdown.changed() => return Ok(None),
+                    }
+                    attempt += 1;
+                }
+                Err(err) => {
+                    self.state.send_replace(ConnectionState::Lost);
+                    return Err(err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INITIAL: Duration = Duration::from_millis(100);
+    const MAX: Duration = Duration::from_millis(1_000);
+
+    fn backoff() -> Backoff {
+        Backoff::new(&ReconnectConfig {
+            initial_delay_ms: INITIAL.as_millis() as u64,
+            max_delay_ms: MAX.as_millis() as u64,
+        })
+    }
+
+    fn assert_jittered(delay: Duration, ceiling: Duration) {
+        assert!(
+            ceiling / 2 <= delay && delay <= ceiling,
+            "{delay:?} is not within {:?} and {ceiling:?}",
+            ceiling / 2
+        );
+    }
+
+    #[test]
+    fn delays_double_up_to_the_max() {
+        let mut backoff = backoff();
+        for ceiling in [100, 200, 400, 800, 1_000, 1_000] {
+            assert_jittered(backoff.next_delay(), Duration::from_millis(ceiling));
+        }
+
+        // Long past the point where doubling would overflow
+        for _ in 0..100 {
+            assert_jittered(backoff.next_delay(), MAX);
+        }
+    }
+
+    #[test]
+    fn delays_are_jittered() {
+        let mut backoff = backoff();
+        backoff.failures = 10;
+        let delays: Vec<_> = (0..10).map(|_| backoff.next_delay()).collect();
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut backoff = backoff();
+        for _ in 0..5 {
+            backoff.next_delay();
+        }
+        backoff.reset();
+        assert_jittered(backoff.next_delay(), INITIAL);
+        assert_jittered(backoff.next_delay(), INITIAL * 2);
+    }
+}
@@ -1,49 +1,219 @@
+use crate::client::auth::authenticate;
+use crate::client::tls::build_connector;
+use crate::config::VehicleTarget;
 use crate::error::{GoliathOperatorError, GoliathOperatorResult};
 use futures_util::{SinkExt, StreamExt};
-use goliath_common::{GoliathCommand, GoliathReport};
-use std::net::Ipv4Addr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::Duration;
+use goliath_common::{
+    Capability, Delivery, EncodedMessage, GoliathCodec, GoliathCommand, GoliathMessage,
+    GoliathPayload, GoliathReport, Hello, LinkReport, LinkStats, MAX_MESSAGE_SIZE, MessageFilter,
+    MessageStamper, Negotiated, Role, SessionClock, VideoCodec, VideoOffer, VideoTransport,
+    WireFormat, codec_for_format,
+};
+use std::collections::HashMap;
+use std::net::Ipv6Addr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
-use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
 use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async_tls_with_config};
+
+mod auth;
+mod connection;
+mod tls;
+
+pub(crate) use connection::{ConnectionManager, ConnectionState};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+pub(crate) const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+pub(crate) const PING_INTERVAL: Duration = Duration::from_secs(1);
+// Number of ping/pong exchanges the link statistics are computed over
+const LINK_STATS_WINDOW: usize = 16;
+// Telemetry arriving later than this, relative to the fastest report seen, is dropped
+const MAX_REPORT_AGE: Duration = Duration::from_millis(500);
+
+// Commands waiting for the vehicle's ack, by sequence number
+type PendingAcks = Arc<Mutex<HashMap<u64, (String, Instant)>>>;
+
+const OPERATOR_CAPABILITIES: [Capability; 3] = [
+    Capability::Turret,
+    Capability::Telemetry,
+    Capability::VideoH264,
+];
+
+// In order of preference
+const OPERATOR_VIDEO_CODECS: [VideoCodec; 1] = [VideoCodec::H264];
+
+/// What picks the session back up after a dropped connection, without authenticating again
+#[derive(Clone, Debug)]
+pub(crate) struct SessionResume {
+    token: String,
+    role: Role,
+}
+
+/// State of the receiving half of the connection
+struct IncomingHandler {
+    clock: SessionClock,
+    filter: MessageFilter,
+    pending_acks: PendingAcks,
+    link_stats: Arc<Mutex<LinkStats>>,
+    report_tx: mpsc::Sender<GoliathReport>,
+    payload_tx: mpsc::Sender<GoliathPayload>, // For answering pings
+    last_received_us: Arc<AtomicU64>,
+}
+
+impl IncomingHandler {
+    async fn handle_message(&mut self, msg: GoliathMessage) -> GoliathOperatorResult<()> {
+        let received_us = self.clock.now_us();
+        self.last_received_us.store(received_us, Ordering::Relaxed);
+        let delivery = self.filter.check(&msg);
+        match msg.payload {
+            // Outdated telemetry would only show a state the vehicle already left
+            GoliathPayload::Report(GoliathReport::Telemetry(_)) if delivery != Delivery::Fresh => {
+                log::debug!("Discarding {delivery:?} telemetry #{}", msg.seq);
+            }
+            GoliathPayload::Report(report) => {
+                self.report_tx
+                    .send(report)
+                    .await
+                    .map_err(|err| GoliathOperatorError::TokioSendError(err.to_string()))?;
+            }
+            GoliathPayload::Ack { seq, applied } => {
+                let pending = self
+                    .pending_acks
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .remove(&seq);
+                match pending {
+                    Some((command, sent)) if applied => {
+                        log::info!("{command} applied after {:?}", sent.elapsed())
+                    }
+                    Some((command, _)) => log::warn!("{command} was not applied"),
+                    None => log::debug!("Got ack for unknown command #{seq}"),
+                }
+            }
+            GoliathPayload::Ping => {
+                self.payload_tx
+                    .send(GoliathPayload::Pong {
+                        ping_timestamp_us: msg.timestamp_us,
+                        ping_received_us: received_us,
+                    })
+                    .await
+                    .map_err(|err| GoliathOperatorError::TokioSendError(err.to_string()))?;
+            }
+            GoliathPayload::Pong {
+                ping_timestamp_us,
+                ping_received_us,
+            } => {
+                self.link_stats
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .record(
+                        ping_timestamp_us,
+                        ping_received_us,
+                        msg.timestamp_us,
+                        received_us,
+                    );
+            }
+            GoliathPayload::Command(cmd) => {
+                log::warn!("Got unexpected command from vehicle: {cmd:?}");
+            }
+        }
+
+        Ok(())
+    }
+}
 
 pub(crate) struct GoliathClient {
-    command_tx: mpsc::Sender<GoliathCommand>,
+    payload_tx: mpsc::Sender<GoliathPayload>,
     report_rx: mpsc::Receiver<GoliathReport>,
-    client_task: Option<(oneshot::Sender<()>, JoinHandle<()>)>,
+    video: Option<VideoTransport>,
+    resume: SessionResume,
+    link_stats: Arc<Mutex<LinkStats>>,
+    clock: SessionClock,
+    last_received_us: Arc<AtomicU64>,
+    client_task: Option<(oneshot::Sender<bool>, JoinHandle<()>)>, // Whether to close
 }
 
 impl GoliathClient {
     async fn client_task(
         stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
-        mut command_rx: mpsc::Receiver<GoliathCommand>,
-        report_tx: mpsc::Sender<GoliathReport>,
-        kill_switch_rx: oneshot::Receiver<()>,
+        mut payload_rx: mpsc::Receiver<GoliathPayload>,
+        mut incoming_handler: IncomingHandler,
+        codec: Arc<dyn GoliathCodec>,
+        kill_switch_rx: oneshot::Receiver<bool>,
     ) {
         let (mut stream_tx, mut stream_rx) = stream.split();
+        let clock = incoming_handler.clock;
+        let pending_acks = Arc::clone(&incoming_handler.pending_acks);
 
         let kill_flag = Arc::new(AtomicBool::new(false));
+        let close_flag = Arc::new(AtomicBool::new(false));
         let mut sender_task = tokio::spawn({
             let kill_flag = Arc::clone(&kill_flag);
+            let close_flag = Arc::clone(&close_flag);
+            let codec = Arc::clone(&codec);
+            let pending_acks = Arc::clone(&pending_acks);
             async move {
+                let mut stamper = MessageStamper::new(clock);
+                let mut ping_interval = tokio::time::interval(PING_INTERVAL);
                 loop {
-                    if kill_flag.load(Ordering::Relaxed) {
-                        return GoliathOperatorResult::Ok(());
-                    }
+                    pending_acks
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .retain(|seq, (command, sent)| {
+                            let waiting = sent.elapsed() < ACK_TIMEOUT;
+                            if !waiting {
+                                log::warn!("Command #{seq} was never acknowledged: {command}");
+                            }
+                            waiting
+                        });
 
-                    if let Ok(Some(msg)) =
-                        tokio::time::timeout(Duration::from_millis(10), command_rx.recv()).await
-                    {
-                        stream_tx
-                            .send(Message::Binary(msg.into_bytes()?))
-                            .await
-                            .map_err(Box::new)?;
+                    let payload = if kill_flag.load(Ordering::Relaxed) {
+                        if !close_flag.load(Ordering::Relaxed) {
+                            return GoliathOperatorResult::Ok(());
+                        }
+                        // What was queued before closing still goes out, then the vehicle is
+                        // told the session ended rather than dropped
+                        match payload_rx.try_recv() {
+                            Ok(payload) => payload,
+                            Err(_) => {
+                                stream_tx.send(Message::Close(None)).await.ok();
+                                return GoliathOperatorResult::Ok(());
+                            }
+                        }
+                    } else {
+                        tokio::select! {
+                            maybe_payload = tokio::time::timeout(Duration::from_millis(10), payload_rx.recv()) => {
+                                match maybe_payload {
+                                    Ok(Some(payload)) => payload,
+                                    _ => continue,
+                                }
+                            }
+                            _ = ping_interval.tick() => GoliathPayload::Ping,
+                        }
+                    };
+
+                    let ack_requested =
+                        matches!(&payload, GoliathPayload::Command(cmd) if cmd.needs_ack());
+                    let description = format!("{payload:?}");
+                    let msg = stamper.stamp(payload, ack_requested);
+                    if ack_requested {
+                        pending_acks
+                            .lock()
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .insert(msg.seq, (description, Instant::now()));
                     }
+
+                    let frame = match codec.encode(&msg)? {
+                        EncodedMessage::Binary(bytes) => Message::Binary(bytes),
+                        EncodedMessage::Text(text) => Message::Text(text.into()),
+                    };
+                    stream_tx.send(frame).await.map_err(Box::new)?;
                 }
             }
         });
@@ -72,26 +242,27 @@ impl GoliathClient {
                                     }
                                     return Ok(());
                                 }
-                                Ok(Message::Text(text)) => {
-                                    log::warn!("Got unexpected text: {text}")
+                                Ok(Message::Text(text)) if codec.format() == WireFormat::Json => {
+                                    if let Some(msg) =
+                                        Self::decode_message(codec.as_ref(), text.as_bytes())
+                                    {
+                                        incoming_handler.handle_message(msg).await?;
+                                    }
                                 }
-                                Ok(Message::Binary(bytes)) => {
-                                    match GoliathReport::read_from_bytes(&bytes) {
-                                        Ok(report) => {
-                                            report_tx.send(report).await.map_err(|err| {
-                                                GoliathOperatorError::TokioSendError(
-                                                    err.to_string(),
-                                                )
-                                            })?;
-                                        }
-                                        Err(err) => {
-                                            log::error!(
-                                                "Failed to read command: {err} (command: {:?})",
-                                                bytes
-                                            );
-                                        }
+                                Ok(Message::Binary(bytes))
+                                    if codec.format() == WireFormat::Bitcode =>
+                                {
+                                    if let Some(msg) = Self::decode_message(codec.as_ref(), &bytes)
+                                    {
+                                        incoming_handler.handle_message(msg).await?;
                                     }
                                 }
+                                Ok(Message::Text(_) | Message::Binary(_)) => {
+                                    log::warn!(
+                                        "Got a frame not matching the {:?} wire format, ignoring",
+                                        codec.format()
+                                    );
+                                }
                                 Ok(Message::Frame(_)) => {
                                     log::debug!("Got raw frame message, ignoring");
                                 }
@@ -111,7 +282,7 @@ impl GoliathClient {
         tokio::select! {
             _ = &mut sender_task => {}
             _ = &mut receiver_task => {}
-            _ = kill_switch_rx => {}
+            close = kill_switch_rx => close_flag.store(close.unwrap_or(false), Ordering::Relaxed),
         }
 
         kill_flag.store(true, Ordering::Relaxed);
@@ -120,46 +291,219 @@ impl GoliathClient {
         receiver_task.await.ok();
     }
 
-    pub(crate) async fn try_new(address: Ipv4Addr, port: u16) -> GoliathOperatorResult<Self> {
-        let url = format!("ws://{address}:{port}");
-        let (ws_stream, _) = connect_async(&url).await.expect("Failed to connect");
+    fn decode_message(codec: &dyn GoliathCodec, data: &[u8]) -> Option<GoliathMessage> {
+        codec
+            .decode(data)
+            .inspect_err(|err| log::error!("Failed to read message: {err} (message: {data:?})"))
+            .ok()
+    }
+
+    /// Sends the operator's hello, returning what the vehicle agreed to and whether it resumed
+    /// the session of the token. The video is only offered with a port to receive it on
+    async fn handshake(
+        stream: &mut WebSocketStream<MaybeTlsStream<TcpStream>>,
+        wire_format: WireFormat,
+        resume_token: Option<String>,
+        video_port: Option<u16>,
+    ) -> GoliathOperatorResult<(Negotiated, bool)> {
+        let local_hello = Hello::new(OPERATOR_CAPABILITIES.to_vec(), vec![wire_format])
+            .with_resume_token(resume_token)
+            .with_video_offer(video_port.map(|port| VideoOffer {
+                port,
+                codecs: OPERATOR_VIDEO_CODECS.to_vec(),
+            }));
+        stream
+            .send(Message::Text(local_hello.clone().into_json()?.into()))
+            .await
+            .map_err(Box::new)?;
+
+        let peer_hello = match tokio::time::timeout(HANDSHAKE_TIMEOUT, stream.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => Hello::read_from_json(&text)?,
+            // Too many operators connected, there may be room later
+            Ok(Some(Ok(Message::Close(Some(frame))))) if frame.code == CloseCode::Again => {
+                return Err(GoliathOperatorError::HandshakeError(format!(
+                    "Vehicle is busy: {}",
+                    frame.reason
+                )));
+            }
+            Ok(Some(Ok(Message::Close(Some(frame))))) => {
+                return Err(GoliathOperatorError::IncompatibleError(
+                    frame.reason.to_string(),
+                ));
+            }
+            Ok(Some(Ok(msg))) => {
+                return Err(GoliathOperatorError::IncompatibleError(format!(
+                    "Expected a hello, got {msg:?}"
+                )));
+            }
+            Ok(Some(Err(err))) => return Err(Box::new(err).into()),
+            Ok(None) => {
+                return Err(GoliathOperatorError::HandshakeError(
+                    "Vehicle disconnected during the handshake".to_string(),
+                ));
+            }
+            Err(_) => {
+                return Err(GoliathOperatorError::HandshakeError(
+                    "Timed out waiting for the vehicle's hello".to_string(),
+                ));
+            }
+        };
+
+        let negotiated = local_hello
+            .negotiate(&peer_hello)
+            .map_err(GoliathOperatorError::IncompatibleError)?;
+        log::info!(
+            "Connected to vehicle {:?}, negotiated {negotiated:?}",
+            peer_hello.build
+        );
+
+        let resumed = peer_hello.resume_token.is_some()
+            && peer_hello.resume_token == local_hello.resume_token;
+        Ok((negotiated, resumed))
+    }
+
+    pub(crate) async fn try_new(
+        target: &VehicleTarget,
+        video_port: Option<u16>,
+        resume: Option<&SessionResume>,
+    ) -> GoliathOperatorResult<Self> {
+        let VehicleTarget {
+            host,
+            port,
+            auth,
+            tls,
+            wire_format,
+        } = target;
+        let connector = tls.as_ref().map(build_connector).transpose()?;
+        let scheme = match connector {
+            Some(_) => "wss",
+            None => "ws",
+        };
+        let url = match host.parse::<Ipv6Addr>() {
+            Ok(address) => format!("{scheme}://[{address}]:{port}"),
+            Err(_) => format!("{scheme}://{host}:{port}"),
+        };
+        let ws_config = WebSocketConfig::default()
+            .max_message_size(Some(MAX_MESSAGE_SIZE))
+            .max_frame_size(Some(MAX_MESSAGE_SIZE));
+        let (mut ws_stream, _) =
+            connect_async_tls_with_config(&url, Some(ws_config), false, connector)
+                .await
+                .map_err(Box::new)?;
+        let (negotiated, resumed) = Self::handshake(
+            &mut ws_stream,
+            *wire_format,
+            resume.map(|resume| resume.token.clone()),
+            video_port,
+        )
+        .await?;
+        let resume = match resume {
+            Some(resume) if resumed => {
+                log::info!("Resumed the previous session as {:?}", resume.role);
+                resume.clone()
+            }
+            _ => {
+                let (role, token) = authenticate(&mut ws_stream, auth).await?;
+                SessionResume { token, role }
+            }
+        };
 
-        let (command_tx, command_rx) = mpsc::channel::<GoliathCommand>(10);
+        let (payload_tx, payload_rx) = mpsc::channel::<GoliathPayload>(10);
         let (report_tx, report_rx) = mpsc::channel::<GoliathReport>(10);
-        let (kill_switch_tx, kill_switch_rx) = oneshot::channel::<()>();
+        let (kill_switch_tx, kill_switch_rx) = oneshot::channel::<bool>();
+        let clock = SessionClock::new();
+        let link_stats = Arc::new(Mutex::new(LinkStats::new(LINK_STATS_WINDOW)));
+        let last_received_us = Arc::new(AtomicU64::new(clock.now_us()));
+        let incoming_handler = IncomingHandler {
+            clock,
+            filter: MessageFilter::new(clock, MAX_REPORT_AGE),
+            pending_acks: PendingAcks::default(),
+            link_stats: Arc::clone(&link_stats),
+            report_tx,
+            payload_tx: payload_tx.clone(),
+            last_received_us: Arc::clone(&last_received_us),
+        };
         let client_task = tokio::spawn(Self::client_task(
             ws_stream,
-            command_rx,
-            report_tx,
+            payload_rx,
+            incoming_handler,
+            Arc::from(codec_for_format(negotiated.wire_format)),
             kill_switch_rx,
         ));
         Ok(Self {
-            command_tx,
+            payload_tx,
             report_rx,
+            video: negotiated.video,
+            resume,
+            link_stats,
+            clock,
+            last_received_us,
             client_task: Some((kill_switch_tx, client_task)),
         })
     }
 
+    /// The stream the vehicle sends, None if it doesn't send one to this operator
+    pub(crate) fn video(&self) -> Option<&VideoTransport> {
+        self.video.as_ref()
+    }
+
+    /// The link as measured by the operator's own pings, None until a pong arrived
+    pub(crate) fn link_report(&self) -> Option<LinkReport> {
+        self.link_stats
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .report()
+    }
+
+    /// Time since anything arrived from the vehicle, at least the pongs to the operator's pings
+    pub(crate) fn silence(&self) -> Duration {
+        Duration::from_micros(
+            self.clock
+                .now_us()
+                .saturating_sub(self.last_received_us.load(Ordering::Relaxed)),
+        )
+    }
+
+    pub(crate) fn role(&self) -> Role {
+        self.resume.role
+    }
+
+    pub(crate) fn resume(&self) -> &SessionResume {
+        &self.resume
+    }
+
+    /// Commands the granted role doesn't permit are dropped, the vehicle would reject them
     pub(crate) async fn send_command(&self, command: GoliathCommand) -> GoliathOperatorResult<()> {
-        self.command_tx
-            .send(command)
+        if !self.resume.role.permits(&command) {
+            log::trace!("Not sending {command:?} as {:?}", self.resume.role);
+            return Ok(());
+        }
+
+        self.payload_tx
+            .send(GoliathPayload::Command(command))
             .await
             .map_err(|err| GoliathOperatorError::TokioSendError(err.to_string()))
     }
 
-    pub(crate) fn poll_report(&mut self) -> GoliathOperatorResult<Option<GoliathReport>> {
-        match self.report_rx.try_recv() {
-            Ok(msg) => Ok(Some(msg)),
-            Err(TryRecvError::Empty) => Ok(None),
-            Err(err) => Err(err.into()),
+    /// Ends the session for good, once the queued commands went out. Dropping the client
+    /// instead leaves the vehicle to park the session for resumption
+    pub(crate) async fn close(&mut self) {
+        if let Some((kill_switch_tx, task_handle)) = self.client_task.take() {
+            kill_switch_tx.send(true).ok();
+            task_handle.await.ok();
         }
     }
+
+    /// None once the connection to the vehicle is gone
+    pub(crate) async fn next_report(&mut self) -> Option<GoliathReport> {
+        self.report_rx.recv().await
+    }
 }
 
 impl Drop for GoliathClient {
     fn drop(&mut self) {
         if let Some((kill_switch_tx, _task_handle)) = self.client_task.take() {
-            kill_switch_tx.send(()).ok();
+            kill_switch_tx.send(false).ok();
         }
     }
 }
@@ -0,0 +1,131 @@
+use crate::config::TlsConfig;
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+use ring::digest::{SHA256, digest};
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::ring::default_provider;
+use rustls::crypto::{CryptoProvider, verify_tls12_signature, verify_tls13_signature};
+use rustls::{CertificateError, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use std::sync::Arc;
+use tokio_tungstenite::Connector;
+
+/// Accepts the vehicle's certificate only if it matches the pinned fingerprint
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    fingerprint: [u8; 32],
+    // Also checks the chain and the name when a CA is configured
+    ca_verifier: Option<Arc<WebPkiServerVerifier>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(ca_verifier) = &self.ca_verifier {
+            ca_verifier.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                ocsp_response,
+                now,
+            )?;
+        }
+
+        if digest(&SHA256, end_entity.as_ref()).as_ref() != self.fingerprint {
+            log::error!("Vehicle certificate does not match the pinned fingerprint");
+            return Err(rustls::Error::InvalidCertificate(
+                CertificateError::ApplicationVerificationFailure,
+            ));
+        }
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+pub(crate) fn build_connector(config: &TlsConfig) -> GoliathOperatorResult<Connector> {
+    let provider = Arc::new(default_provider());
+
+    let ca_verifier = match &config.ca_cert_path {
+        Some(ca_cert_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in CertificateDer::pem_file_iter(ca_cert_path)? {
+                roots.add(cert?)?;
+            }
+            let verifier =
+                WebPkiServerVerifier::builder_with_provider(Arc::new(roots), Arc::clone(&provider))
+                    .build()
+                    .map_err(|err| GoliathOperatorError::ConfigError(err.to_string()))?;
+            Some(verifier)
+        }
+        None => None,
+    };
+
+    let verifier: Arc<dyn ServerCertVerifier> = match config.pinned_fingerprint() {
+        Some(fingerprint) => Arc::new(PinnedCertVerifier {
+            fingerprint,
+            ca_verifier,
+            provider: Arc::clone(&provider),
+        }),
+        None => ca_verifier.ok_or_else(|| {
+            GoliathOperatorError::ConfigError("No way to trust the vehicle".to_string())
+        })?,
+    };
+
+    // Only the pinned verifier is custom, the CA one is the verifier rustls would use anyway
+    let builder = rustls::ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(verifier);
+    let client_config = match (&config.client_cert_path, &config.client_key_path) {
+        (Some(cert_path), Some(key_path)) => {
+            let certs = CertificateDer::pem_file_iter(cert_path)?.collect::<Result<Vec<_>, _>>()?;
+            let key = PrivateKeyDer::from_pem_file(key_path)?;
+            builder.with_client_auth_cert(certs, key)?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+
+    Ok(Connector::Rustls(Arc::new(client_config)))
+}
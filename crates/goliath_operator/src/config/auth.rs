@@ -0,0 +1,42 @@
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+use goliath_common::Role;
+use std::path::Path;
+
+/// Credentials presented to the vehicle, which may not require any
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct AuthConfig {
+    pub(crate) role: Role,
+    pub(crate) token: Option<String>,
+    // PEM PKCS#8 ed25519 key, as generated by `openssl genpkey -algorithm ed25519`
+    pub(crate) private_key_path: Option<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            role: Role::Driver,
+            token: None,
+            private_key_path: None,
+        }
+    }
+}
+
+impl AuthConfig {
+    pub(crate) fn validate(&self) -> GoliathOperatorResult<()> {
+        if self.token.is_some() && self.private_key_path.is_some() {
+            return Err(GoliathOperatorError::ConfigError(
+                "Only one of the auth token and private key can be set".to_string(),
+            ));
+        }
+        if let Some(path) = &self.private_key_path
+            && !Path::new(path).is_file()
+        {
+            return Err(GoliathOperatorError::ConfigError(format!(
+                "Private key {path} does not exist"
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+use std::net::{Ipv4Addr, SocketAddr};
+
+/// The controller, which sends its state as JSON datagrams
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct InputConfig {
+    // Where the operator listens for the controller
+    pub(crate) listen: SocketAddr,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 6000)),
+        }
+    }
+}
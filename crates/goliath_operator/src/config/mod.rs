@@ -0,0 +1,109 @@
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+use goliath_common::WireFormat;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+mod auth;
+mod input;
+mod reconnect;
+mod tls;
+mod vehicle;
+mod video;
+
+pub(crate) use auth::AuthConfig;
+pub(crate) use input::InputConfig;
+pub(crate) use reconnect::ReconnectConfig;
+pub(crate) use tls::TlsConfig;
+pub(crate) use vehicle::{VehicleProfile, VehicleTarget};
+pub(crate) use video::{VideoConfig, VideoSink};
+
+pub(crate) const DEFAULT_CONFIG_PATH: &str = "goliath_operator.toml";
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct OperatorConfig {
+    // Used when no vehicle is given on the command line
+    pub(crate) default_vehicle: Option<String>,
+    pub(crate) vehicles: BTreeMap<String, VehicleProfile>,
+    pub(crate) video: VideoConfig,
+    pub(crate) input: InputConfig,
+    pub(crate) reconnect: ReconnectConfig,
+    // For the vehicles whose profile doesn't set its own
+    pub(crate) auth: AuthConfig,
+    // Plain websocket if absent
+    pub(crate) tls: Option<TlsConfig>,
+    // Encoding of the control messages, JSON is bigger but readable when debugging
+    pub(crate) wire_format: WireFormat,
+}
+
+impl OperatorConfig {
+    /// Loads the config, falling back to the defaults if the file does not exist. Left to the
+    /// caller to validate, once any overrides are applied
+    pub(crate) fn load(path: impl AsRef<Path>) -> GoliathOperatorResult<Self> {
+        let path = path.as_ref();
+        let config = if path.exists() {
+            log::info!("Loading config from {}", path.display());
+            toml::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            log::warn!("No config found at {}, using defaults", path.display());
+            Self::default()
+        };
+
+        Ok(config)
+    }
+
+    pub(crate) fn validate(&self) -> GoliathOperatorResult<()> {
+        if let Some(name) = &self.default_vehicle
+            && !self.vehicles.contains_key(name)
+        {
+            return Err(GoliathOperatorError::ConfigError(format!(
+                "Default vehicle {name} has no profile"
+            )));
+        }
+        for profile in self.vehicles.values() {
+            profile.validate()?;
+        }
+        self.video.validate()?;
+        self.reconnect.validate()?;
+        self.auth.validate()?;
+        if let Some(tls) = &self.tls {
+            tls.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// The named profile, or the default one. A single profile is the default without saying so
+    pub(crate) fn profile(&self, name: Option<&str>) -> GoliathOperatorResult<&VehicleProfile> {
+        let mut names = self.vehicles.keys();
+        let name = match (
+            name.or(self.default_vehicle.as_deref()),
+            names.next(),
+            names.next(),
+        ) {
+            (Some(name), _, _) => name,
+            (None, Some(only), None) => only,
+            (None, _, _) => {
+                return Err(GoliathOperatorError::ConfigError(
+                    "No vehicle given, pass --vehicle or --host, or set a default vehicle"
+                        .to_string(),
+                ));
+            }
+        };
+
+        self.vehicles.get(name).ok_or_else(|| {
+            GoliathOperatorError::ConfigError(format!("Vehicle {name} has no profile"))
+        })
+    }
+
+    /// Fills in the credentials the profile doesn't set
+    pub(crate) fn target(&self, profile: VehicleProfile) -> VehicleTarget {
+        VehicleTarget {
+            host: profile.host,
+            port: profile.port,
+            auth: profile.auth.unwrap_or_else(|| self.auth.clone()),
+            tls: profile.tls.or_else(|| self.tls.clone()),
+            wire_format: self.wire_format,
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+use std::time::Duration;
+
+/// Delays between attempts to reach the vehicle, doubling from the initial one up to the max
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ReconnectConfig {
+    pub(crate) initial_delay_ms: u64,
+    pub(crate) max_delay_ms: u64,
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            initial_delay_ms: 500,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl ReconnectConfig {
+    pub(crate) fn validate(&self) -> GoliathOperatorResult<()> {
+        if self.initial_delay_ms == 0 {
+            return Err(GoliathOperatorError::ConfigError(
+                "Initial reconnect delay must be positive".to_string(),
+            ));
+        }
+        if self.max_delay_ms < self.initial_delay_ms {
+            return Err(GoliathOperatorError::ConfigError(
+                "Max reconnect delay must not be shorter than the initial one".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn initial_delay(&self) -> Duration {
+        Duration::from_millis(self.initial_delay_ms)
+    }
+
+    pub(crate) fn max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay_ms)
+    }
+}
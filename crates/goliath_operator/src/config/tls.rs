@@ -0,0 +1,75 @@
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+use std::path::Path;
+
+/// Connects to the vehicle over TLS when present in the config
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TlsConfig {
+    // PEM, CAs the vehicle's certificate must be signed by
+    pub(crate) ca_cert_path: Option<String>,
+    // SHA-256 of the vehicle's certificate in hex, colons allowed. Enough on its own for a
+    // self-signed certificate, checked on top of the CA if both are set
+    pub(crate) pinned_cert_sha256: Option<String>,
+    // PEM, presented to vehicles that require mutual TLS
+    pub(crate) client_cert_path: Option<String>,
+    pub(crate) client_key_path: Option<String>,
+}
+
+impl TlsConfig {
+    pub(crate) fn validate(&self) -> GoliathOperatorResult<()> {
+        if self.ca_cert_path.is_none() && self.pinned_cert_sha256.is_none() {
+            return Err(GoliathOperatorError::ConfigError(
+                "TLS needs a CA certificate or a pinned certificate to trust the vehicle"
+                    .to_string(),
+            ));
+        }
+        if self.client_cert_path.is_some() != self.client_key_path.is_some() {
+            return Err(GoliathOperatorError::ConfigError(
+                "Client certificate and key must be set together".to_string(),
+            ));
+        }
+        if let Some(pin) = &self.pinned_cert_sha256
+            && parse_fingerprint(pin).is_none()
+        {
+            return Err(GoliathOperatorError::ConfigError(format!(
+                "Pinned certificate {pin} is not a hex SHA-256 fingerprint"
+            )));
+        }
+
+        let paths = [
+            &self.ca_cert_path,
+            &self.client_cert_path,
+            &self.client_key_path,
+        ];
+        for path in paths.into_iter().flatten() {
+            if !Path::new(path).is_file() {
+                return Err(GoliathOperatorError::ConfigError(format!(
+                    "TLS file {path} does not exist"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn pinned_fingerprint(&self) -> Option<[u8; 32]> {
+        self.pinned_cert_sha256
+            .as_deref()
+            .and_then(parse_fingerprint)
+    }
+}
+
+/// Accepts both `ab12...` and the `AB:12:...` form printed by openssl
+fn parse_fingerprint(fingerprint: &str) -> Option<[u8; 32]> {
+    let hex: String = fingerprint.chars().filter(|c| *c != ':').collect();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut bytes = [0; 32];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(bytes)
+}
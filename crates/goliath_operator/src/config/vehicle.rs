@@ -0,0 +1,66 @@
+use crate::config::{AuthConfig, TlsConfig};
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+use goliath_common::WireFormat;
+
+const DEFAULT_PORT: u16 = 5000;
+
+/// A vehicle known by name, its credentials override the top level ones
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct VehicleProfile {
+    // IPv4 or IPv6 address, or a name to resolve
+    pub(crate) host: String,
+    #[serde(default = "default_port")]
+    pub(crate) port: u16,
+    #[serde(default)]
+    pub(crate) auth: Option<AuthConfig>,
+    #[serde(default)]
+    pub(crate) tls: Option<TlsConfig>,
+}
+
+fn default_port() -> u16 {
+    DEFAULT_PORT
+}
+
+impl VehicleProfile {
+    /// A vehicle without a profile, given on the command line
+    pub(crate) fn from_host(host: String) -> Self {
+        Self {
+            host,
+            port: DEFAULT_PORT,
+            auth: None,
+            tls: None,
+        }
+    }
+
+    pub(crate) fn validate(&self) -> GoliathOperatorResult<()> {
+        if self.host.is_empty() {
+            return Err(GoliathOperatorError::ConfigError(
+                "Vehicle host must be set".to_string(),
+            ));
+        }
+        if self.port == 0 {
+            return Err(GoliathOperatorError::ConfigError(
+                "Vehicle port must be positive".to_string(),
+            ));
+        }
+        if let Some(auth) = &self.auth {
+            auth.validate()?;
+        }
+        if let Some(tls) = &self.tls {
+            tls.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The vehicle to connect to, with the credentials resolved
+#[derive(Clone, Debug)]
+pub(crate) struct VehicleTarget {
+    pub(crate) host: String,
+    pub(crate) port: u16,
+    pub(crate) auth: AuthConfig,
+    pub(crate) tls: Option<TlsConfig>,
+    pub(crate) wire_format: WireFormat,
+}
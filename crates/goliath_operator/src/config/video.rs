@@ -0,0 +1,51 @@
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
+
+/// Where the decoded video is shown
+#[derive(Copy, Clone, Debug, Default, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum VideoSink {
+    #[default]
+    Ximage,
+    Wayland,
+    // Decodes without showing anything, for headless testing
+    Fake,
+}
+
+impl VideoSink {
+    pub(crate) fn element_name(&self) -> &'static str {
+        match self {
+            Self::Ximage => "ximagesink",
+            Self::Wayland => "waylandsink",
+            Self::Fake => "fakesink",
+        }
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct VideoConfig {
+    // Announced to the vehicle, which sends the RTP stream there
+    pub(crate) port: u16,
+    pub(crate) sink: VideoSink,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            port: 9000,
+            sink: VideoSink::default(),
+        }
+    }
+}
+
+impl VideoConfig {
+    pub(crate) fn validate(&self) -> GoliathOperatorResult<()> {
+        if self.port == 0 {
+            return Err(GoliathOperatorError::ConfigError(
+                "Video port must be positive".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use goliath_common::{GoliathSerdeError, GoliathTracingError, GoliathVideoError};
 use gstreamer::glib;
+use tokio_tungstenite::tungstenite;
+use tokio_tungstenite::tungstenite::error::ProtocolError;
 
 pub(crate) type GoliathOperatorResult<T> = Result<T, GoliathOperatorError>;
 
@@ -33,6 +35,27 @@ pub(crate) enum GoliathOperatorError {
     #[error("Tokio Send error: {0}")]
     TokioSendError(String),
 
+    #[error("Handshake error: {0}")]
+    HandshakeError(String),
+
+    #[error("Incompatible vehicle: {0}")]
+    IncompatibleError(String),
+
+    #[error("Authentication error: {0}")]
+    AuthenticationError(String),
+
+    #[error("TLS error: {0}")]
+    TlsError(#[from] rustls::Error),
+
+    #[error("Error reading PEM file: {0}")]
+    PemError(#[from] rustls_pki_types::pem::Error),
+
+    #[error("Config error: {0}")]
+    ConfigError(String),
+
+    #[error("Config parsing error: {0}")]
+    ConfigParseError(#[from] toml::de::Error),
+
     #[error("Error while initializing logging/tracing: {0}")]
     TracingInitError(#[from] GoliathTracingError),
 
@@ -48,3 +71,72 @@ pub(crate) enum GoliathOperatorError {
     #[error("GStreamer initialization error: {0}")]
     GlibBoolError(#[from] glib::BoolError),
 }
+
+impl GoliathOperatorError {
+    /// Whether connecting again could succeed, the vehicle being down or the link flaky. A
+    /// rejected authentication, an incompatible vehicle or a broken config would fail the same
+    /// way every time
+    pub(crate) fn is_transient(&self) -> bool {
+        match self {
+            Self::IoError(_) | Self::HandshakeError(_) => true,
+            // TLS and HTTP failures are a misconfiguration, or not a vehicle at all
+            Self::WSError(err) => matches!(
+                **err,
+                tungstenite::Error::Io(_)
+                    | tungstenite::Error::ConnectionClosed
+                    | tungstenite::Error::AlreadyClosed
+                    | tungstenite::Error::Protocol(ProtocolError::ResetWithoutClosingHandshake)
+            ),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::tungstenite::error::TlsError;
+    use tokio_tungstenite::tungstenite::http::Response;
+
+    fn ws_error(err: tungstenite::Error) -> GoliathOperatorError {
+        Box::new(err).into()
+    }
+
+    #[test]
+    fn connection_failures_are_transient() {
+        let refused = || std::io::Error::from(std::io::ErrorKind::ConnectionRefused);
+        assert!(GoliathOperatorError::from(refused()).is_transient());
+        assert!(ws_error(tungstenite::Error::Io(refused())).is_transient());
+        assert!(ws_error(tungstenite::Error::ConnectionClosed).is_transient());
+        assert!(
+            ws_error(tungstenite::Error::Protocol(
+                ProtocolError::ResetWithoutClosingHandshake
+            ))
+            .is_transient()
+        );
+        assert!(
+            GoliathOperatorError::HandshakeError(
+                "Timed out waiting for the vehicle's hello".into()
+            )
+            .is_transient()
+        );
+    }
+
+    #[test]
+    fn rejections_are_fatal() {
+        let tls = TlsError::Rustls(Box::new(rustls::Error::InvalidCertificate(
+            rustls::CertificateError::UnknownIssuer,
+        )));
+        assert!(!ws_error(tungstenite::Error::Tls(tls)).is_transient());
+        let forbidden = Response::builder().status(403).body(None).unwrap();
+        assert!(!ws_error(tungstenite::Error::Http(forbidden.into())).is_transient());
+        assert!(
+            !GoliathOperatorError::IncompatibleError(
+                "Protocol version 8 is not supported, expected 9".into()
+            )
+            .is_transient()
+        );
+        assert!(!GoliathOperatorError::AuthenticationError("Unknown token".into()).is_transient());
+        assert!(!GoliathOperatorError::ConfigError("No vehicle given".into()).is_transient());
+    }
+}
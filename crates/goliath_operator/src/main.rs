@@ -1,28 +1,243 @@
+mod cli;
 mod client;
+mod config;
 mod error;
 mod session;
 mod video;
 
-use crate::client::GoliathClient;
-use crate::error::GoliathOperatorResult;
+use crate::cli::{Cli, Command};
+use crate::client::{
+    ACK_TIMEOUT, ConnectionManager, ConnectionState, GoliathClient, PING_INTERVAL,
+};
+use crate::config::{InputConfig, OperatorConfig, VehicleTarget};
+use crate::error::{GoliathOperatorError, GoliathOperatorResult};
 use crate::session::GoliathOperatorSession;
-use goliath_common::{common_init_for_trace, initiate_gstreamer, start_main_loop};
-use std::net::Ipv4Addr;
+use clap::Parser;
+use goliath_common::{
+    GoliathCommand, GoliathReport, MotorCommand, Role, Track, TrackCalibration,
+    common_init_for_trace, initiate_gstreamer, start_main_loop,
+};
+use std::time::Duration;
 use tokio::runtime::Handle;
+use tokio::sync::watch;
+
+// For the vehicle to grant the lease, once connected
+const LEASE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() -> GoliathOperatorResult<()> {
+    let cli = Cli::parse();
     common_init_for_trace()?;
     initiate_gstreamer()?;
 
-    loop {
-        log::info!("Attempting new connection");
-        // TODO: Replace this with clap arg, then with a wireguard-provided address
-        let client_ws = GoliathClient::try_new(Ipv4Addr::new(192, 168, 0, 100), 5000).await?;
-        log::info!("Connected. creating session");
-        let mut session_ctx = GoliathOperatorSession::try_new(client_ws)?;
+    let (config, mut target) = cli.load_config()?;
+
+    // Ctrl-C ends the session the way the operator would, the vehicle is left stopped
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            log::info!("Shutting down");
+            shutdown_tx.send_replace(true);
+        }
+    });
+
+    match cli.command {
+        Command::Drive { .. } => {
+            run_sessions(target, &config, Some(&config.input), shutdown_rx).await
+        }
+        // Watching or pinging never needs more, whatever the credentials would allow
+        Command::Watch { .. } => {
+            target.auth.role = Role::Observer;
+            run_sessions(target, &config, None, shutdown_rx).await
+        }
+        Command::Ping { count } => {
+            target.auth.role = Role::Observer;
+            ping(target, &config, count, shutdown_rx).await
+        }
+        Command::Calibrate { calibration } => {
+            let (track, calibration) = calibration.calibration();
+            calibrate(target, &config, track, calibration, shutdown_rx).await
+        }
+    }
+}
+
+/// Logs every transition, until the manager is gone
+fn log_connection_state(manager: &ConnectionManager, target: &VehicleTarget) {
+    let mut state_rx = manager.subscribe();
+    let vehicle = format!("{}:{}", target.host, target.port);
+    tokio::spawn(async move {
+        while state_rx.changed().await.is_ok() {
+            let state = *state_rx.borrow_and_update();
+            match state {
+                ConnectionState::Degraded | ConnectionState::Lost => {
+                    log::warn!("Connection to {vehicle} {state}")
+                }
+                _ => log::info!("Connection to {vehicle} {state}"),
+            }
+        }
+    });
+}
+
+/// Without an input, only watches the vehicle. Reconnects until shutting down
+async fn run_sessions(
+    target: VehicleTarget,
+    config: &OperatorConfig,
+    input: Option<&InputConfig>,
+    shutdown: watch::Receiver<bool>,
+) -> GoliathOperatorResult<()> {
+    let mut manager = ConnectionManager::new(
+        target.clone(),
+        Some(config.video.port),
+        &config.reconnect,
+        shutdown.clone(),
+    );
+    log_connection_state(&manager, &target);
+    while let Some(client_ws) = manager.connect().await? {
+        let mut session_ctx = GoliathOperatorSession::try_new(
+            client_ws,
+            &config.video,
+            input,
+            manager.state(),
+            shutdown.clone(),
+        )?;
 
         Handle::current().spawn_blocking(start_main_loop);
         tokio::spawn(async move { session_ctx.run().await }).await??;
     }
+
+    Ok(())
+}
+
+/// Logs the link statistics once per ping the client sends on its own
+async fn ping(
+    target: VehicleTarget,
+    config: &OperatorConfig,
+    count: u32,
+    mut shutdown: watch::Receiver<bool>,
+) -> GoliathOperatorResult<()> {
+    // No video offered, the vehicle has no reason to send it
+    let mut manager =
+        ConnectionManager::new(target.clone(), None, &config.reconnect, shutdown.clone());
+    log_connection_state(&manager, &target);
+    let Some(mut client) = manager.connect().await? else {
+        return Ok(());
+    };
+
+    let mut interval = tokio::time::interval(PING_INTERVAL);
+    interval.tick().await; // The first tick is immediate
+    for _ in 0..count {
+        // Reports are drained, the connection would stall on them otherwise
+        loop {
+            tokio::select! {
+                report = client.next_report() => {
+                    if report.is_none() {
+                        return Err(GoliathOperatorError::GeneralError(
+                            "Lost the connection to the vehicle".to_string(),
+                        ));
+                    }
+                }
+                _ = shutdown.changed() => {
+                    client.close().await;
+                    return Ok(());
+                }
+                _ = interval.tick() => break,
+            }
+        }
+
+        match client.link_report() {
+            Some(link) => log::info!(
+                "Round trip {:?}, jitter {:?} over {} pings",
+                Duration::from_micros(link.rtt_us),
+                Duration::from_micros(link.jitter_us),
+                link.samples
+            ),
+            None => log::warn!("No pong from the vehicle yet"),
+        }
+    }
+
+    client.close().await;
+    Ok(())
+}
+
+/// Sends the calibration while holding the driving lease, which is released again after
+async fn calibrate(
+    target: VehicleTarget,
+    config: &OperatorConfig,
+    track: Track,
+    calibration: TrackCalibration,
+    mut shutdown: watch::Receiver<bool>,
+) -> GoliathOperatorResult<()> {
+    let command = GoliathCommand::Motor(MotorCommand::Calibrate {
+        track,
+        calibration: calibration.clone(),
+    });
+    if !target.auth.role.permits(&command) {
+        return Err(GoliathOperatorError::ConfigError(format!(
+            "Calibrating is not permitted as {:?}",
+            target.auth.role
+        )));
+    }
+
+    let mut manager =
+        ConnectionManager::new(target.clone(), None, &config.reconnect, shutdown.clone());
+    log_connection_state(&manager, &target);
+    let Some(mut client) = manager.connect().await? else {
+        return Ok(());
+    };
+
+    client.send_command(GoliathCommand::TakeControl).await?;
+    let result = send_with_lease(&mut client, command, &mut shutdown).await;
+    client.send_command(GoliathCommand::ReleaseControl).await?;
+    client.close().await;
+
+    if result? {
+        log::info!(
+            "{track:?} track calibrated to {calibration:?} until the vehicle restarts, set it in \
+             the motors calibration of the vehicle config to keep it"
+        );
+    }
+    Ok(())
+}
+
+/// Whether the command was sent and not rejected, false if shutting down first
+async fn send_with_lease(
+    client: &mut GoliathClient,
+    command: GoliathCommand,
+    shutdown: &mut watch::Receiver<bool>,
+) -> GoliathOperatorResult<bool> {
+    let deadline = tokio::time::sleep(LEASE_TIMEOUT);
+    tokio::pin!(deadline);
+    let mut sent = false;
+    loop {
+        tokio::select! {
+            report = client.next_report() => match report {
+                Some(GoliathReport::DrivingLease { yours: true, .. }) if !sent => {
+                    client.send_command(command.clone()).await?;
+                    sent = true;
+                    // A rejection would arrive along with the ack
+                    deadline.as_mut().reset(tokio::time::Instant::now() + ACK_TIMEOUT);
+                }
+                Some(GoliathReport::CommandRejected { command, reason }) => {
+                    return Err(GoliathOperatorError::GeneralError(format!(
+                        "Vehicle rejected {command}: {reason:?}"
+                    )));
+                }
+                Some(_) => {}
+                None => {
+                    return Err(GoliathOperatorError::GeneralError(
+                        "Lost the connection to the vehicle".to_string(),
+                    ));
+                }
+            },
+            _ = &mut deadline => {
+                if !sent {
+                    return Err(GoliathOperatorError::GeneralError(
+                        "Vehicle did not grant the driving lease".to_string(),
+                    ));
+                }
+                return Ok(true);
+            }
+            _ = shutdown.changed() => return Ok(false),
+        }
+    }
 }
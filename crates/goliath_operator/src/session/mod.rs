@@ -1,80 +1,220 @@
-use crate::client::GoliathClient;
+use crate::client::{ConnectionState, GoliathClient, PING_INTERVAL};
+use crate::config::{InputConfig, VideoConfig};
 use crate::error::GoliathOperatorResult;
 use crate::video::OperatorPipeline;
-use goliath_common::{GoliathCommand, GoliathGstPipeline, MotorCommand, stop_main_loop};
+use goliath_common::{
+    GoliathCommand, GoliathGstPipeline, GoliathReport, MotorCommand, VideoCodec, VideoTransport,
+    stop_main_loop,
+};
 use std::io::ErrorKind;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::UdpSocket;
+use tokio::sync::watch;
+
+// Must be comfortably shorter than the vehicle's motors watchdog timeout
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(200);
+// Several pongs missing in a row
+const DEGRADED_AFTER: Duration = PING_INTERVAL.saturating_mul(3);
+// The connection is given up on, for a new one to be made
+const LOST_AFTER: Duration = DEGRADED_AFTER.saturating_mul(3);
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct ControllerInfo {
+    thrust: f32,
+    steer: f32,
+    // Only sent by controllers with an emergency stop button, on change
+    #[serde(default)]
+    emergency_stop: Option<bool>,
+    // Only sent by controllers with a take/release control button, on change
+    #[serde(default)]
+    control: Option<bool>,
+}
 
 pub(crate) struct GoliathOperatorSession {
     client_conn: GoliathClient,
-    operator_pipeline: Arc<OperatorPipeline>,
+    operator_pipeline: Option<OperatorPipeline>, // Only if the vehicle sends the video
+    controller_address: Option<SocketAddr>,      // Only watching without one
+    has_lease: bool,                             // As last reported by the vehicle
+    connection_state: Arc<watch::Sender<ConnectionState>>,
+    shutdown: watch::Receiver<bool>,
 }
 
 impl GoliathOperatorSession {
-    pub(crate) fn try_new(client_conn: GoliathClient) -> GoliathOperatorResult<Self> {
-        let operator_pipeline = Arc::new(OperatorPipeline::try_new(
-            gstreamer::Caps::builder("application/x-rtp")
-                .field("encoding-name", "H264")
-                .build(),
-        )?);
+    pub(crate) fn try_new(
+        client_conn: GoliathClient,
+        video: &VideoConfig,
+        input: Option<&InputConfig>,
+        connection_state: Arc<watch::Sender<ConnectionState>>,
+        shutdown: watch::Receiver<bool>,
+    ) -> GoliathOperatorResult<Self> {
+        let operator_pipeline = client_conn
+            .video()
+            .map(|transport| OperatorPipeline::try_new(rtp_caps(transport), video))
+            .transpose()?;
         Ok(Self {
             client_conn,
             operator_pipeline,
+            controller_address: input.map(|input| input.listen),
+            has_lease: false,
+            connection_state,
+            shutdown,
         })
     }
 
+    fn handle_report(&mut self, report: GoliathReport) {
+        match report {
+            GoliathReport::EmergencyStop { engaged } => {
+                log::warn!("Vehicle emergency stop engaged: {engaged}");
+            }
+            GoliathReport::LinkHealth(vehicle_link) => {
+                log::info!(
+                    "Link health, vehicle side: {vehicle_link:?}, operator side: {:?}",
+                    self.client_conn.link_report()
+                );
+            }
+            GoliathReport::DrivingLease { holder, yours } => {
+                self.has_lease = yours;
+                match holder {
+                    _ if yours => log::info!("Holding the driving lease"),
+                    Some(holder) => log::info!("Driving lease held by {holder}"),
+                    None => log::info!("Driving lease is free"),
+                }
+            }
+            GoliathReport::Telemetry(telemetry) => {
+                log::debug!("Vehicle telemetry: {telemetry:?}");
+            }
+            report => {
+                log::info!("Received report: {report:?}");
+            }
+        }
+    }
+
+    /// Returns false once the vehicle has been silent for too long to still be there
+    fn check_link(&self) -> bool {
+        let silence = self.client_conn.silence();
+        if silence > LOST_AFTER {
+            log::warn!("Nothing received from the vehicle for {silence:?}, dropping the link");
+            return false;
+        }
+
+        let state = if silence > DEGRADED_AFTER {
+            ConnectionState::Degraded
+        } else {
+            ConnectionState::Connected
+        };
+        self.connection_state.send_if_modified(|current| {
+            let changed = *current != state;
+            *current = state;
+            changed
+        });
+        true
+    }
+
+    async fn handle_controller(&mut self, msg: ControllerInfo) -> GoliathOperatorResult<()> {
+        log::info!("Got msg: {msg:?}");
+        let emergency_stop_cmd = match msg.emergency_stop {
+            Some(true) => Some(GoliathCommand::EmergencyStop),
+            Some(false) => Some(GoliathCommand::ClearEmergencyStop),
+            None => None,
+        };
+        if let Some(cmd) = emergency_stop_cmd {
+            self.client_conn.send_command(cmd).await?;
+        }
+
+        let control_cmd = match msg.control {
+            Some(true) => Some(GoliathCommand::TakeControl),
+            Some(false) => Some(GoliathCommand::ReleaseControl),
+            None => None,
+        };
+        if let Some(cmd) = control_cmd {
+            self.client_conn.send_command(cmd).await?;
+        }
+
+        // The vehicle would only reject it
+        if !self.has_lease {
+            return Ok(());
+        }
+
+        self.client_conn
+            .send_command(GoliathCommand::Motor(MotorCommand::Drive {
+                thrust: msg.thrust,
+                steer: msg.steer,
+            }))
+            .await
+    }
+
     pub(crate) async fn run(&mut self) -> GoliathOperatorResult<()> {
-        log::info!("Starting Session");
-        self.operator_pipeline.start_pipeline(None)?;
+        log::info!("Starting Session as {:?}", self.client_conn.role());
+        match &self.operator_pipeline {
+            Some(operator_pipeline) => operator_pipeline.start_pipeline(None)?,
+            None => log::warn!("Vehicle does not stream video, running without video"),
+        }
+
+        let controller_socket = match self.controller_address {
+            Some(address) => Some(UdpSocket::bind(address).await?),
+            None => None,
+        };
+
+        // Observers only watch, the others ask to drive right away
+        if controller_socket.is_some()
+            && self
+                .client_conn
+                .role()
+                .permits(&GoliathCommand::TakeControl)
+        {
+            self.client_conn
+                .send_command(GoliathCommand::TakeControl)
+                .await?;
+        }
 
-        let controller_socket = UdpSocket::bind("0.0.0.0:6000").await?;
         let mut mtu_buffer = [0u8; 1400];
+        let mut heartbeat_interval = tokio::time::interval(HEARTBEAT_INTERVAL);
+        let mut link_check_interval = tokio::time::interval(PING_INTERVAL);
 
         // Main loop
+        let mut link_lost = false;
         loop {
-            match self.client_conn.poll_report() {
-                Ok(Some(report)) => {
-                    log::info!("Received report: {report:?}");
+            tokio::select! {
+                report = self.client_conn.next_report() => {
+                    match report {
+                        Some(report) => self.handle_report(report),
+                        None => break,
+                    }
+                    continue;
                 }
-                Ok(_) => {}
-                Err(err) => {
-                    log::error!("Error while polling reports: {err}");
+                _ = self.shutdown.changed() => {
+                    log::info!("Ending the session");
                     break;
                 }
+                _ = link_check_interval.tick() => {
+                    if !self.check_link() {
+                        link_lost = true;
+                        break;
+                    }
+                    continue;
+                }
+                readable = readable(controller_socket.as_ref()) => readable?,
+                _ = heartbeat_interval.tick(), if self.has_lease => {
+                    if let Err(e) = self.client_conn.send_command(GoliathCommand::Heartbeat).await {
+                        log::error!("Error while sending heartbeat: {e}");
+                        break;
+                    }
+                    continue;
+                }
             }
 
-            controller_socket.readable().await?;
-
+            let Some(controller_socket) = &controller_socket else {
+                continue;
+            };
             match controller_socket.try_recv(&mut mtu_buffer) {
                 Ok(read) => {
-                    let read_slice = &mtu_buffer[..read];
-
-                    #[derive(Debug, serde::Serialize, serde::Deserialize)]
-                    struct ControllerInfo {
-                        thrust: f32,
-                        steer: f32,
-                    }
-
-                    if let Ok(msg) = serde_json::from_slice::<ControllerInfo>(read_slice) {
-                        log::info!("Got msg: {msg:?}");
-                        if let Err(e) = self
-                            .client_conn
-                            .send_command(GoliathCommand::Motor(MotorCommand::Thrust(msg.thrust)))
-                            .await
-                        {
-                            log::error!("Error while sending command: {e}");
-                            break;
-                        }
-
-                        if let Err(e) = self
-                            .client_conn
-                            .send_command(GoliathCommand::Motor(MotorCommand::Steer(msg.steer)))
-                            .await
-                        {
-                            log::error!("Error while sending command: {e}");
-                            break;
-                        }
+                    if let Ok(msg) = serde_json::from_slice::<ControllerInfo>(&mtu_buffer[..read])
+                        && let Err(e) = self.handle_controller(msg).await
+                    {
+                        log::error!("Error while sending command: {e}");
+                        break;
                     }
                 }
                 Err(e) if e.kind() == ErrorKind::WouldBlock => {}
@@ -85,17 +225,57 @@ impl GoliathOperatorSession {
             };
         }
 
-        self.client_conn
-            .send_command(GoliathCommand::Motor(MotorCommand::Thrust(0.0)))
-            .await
-            .ok();
-        self.client_conn
-            .send_command(GoliathCommand::Motor(MotorCommand::Steer(0.0)))
-            .await
-            .ok();
+        if !*self.shutdown.borrow() {
+            self.connection_state.send_replace(ConnectionState::Lost);
+        }
+
+        // Hand the vehicle over stopped, without waiting for the lease to expire. Over a lost
+        // link nothing would arrive, the vehicle parks the session and its lease instead
+        if self.has_lease && !link_lost {
+            self.client_conn
+                .send_command(GoliathCommand::Motor(MotorCommand::Drive {
+                    thrust: 0.0,
+                    steer: 0.0,
+                }))
+                .await
+                .ok();
+            self.client_conn
+                .send_command(GoliathCommand::ReleaseControl)
+                .await
+                .ok();
+        }
+
+        // Otherwise the vehicle keeps the session parked, for the reconnection to resume
+        if *self.shutdown.borrow() {
+            self.client_conn.close().await;
+        }
 
-        self.operator_pipeline.stop_pipeline().ok();
+        if let Some(operator_pipeline) = &self.operator_pipeline {
+            operator_pipeline.stop_pipeline().ok();
+        }
         stop_main_loop();
         Ok(())
     }
 }
+
+/// The stream exactly as the vehicle announced it, anything else arriving on the port is not it
+fn rtp_caps(transport: &VideoTransport) -> gstreamer::Caps {
+    let encoding_name = match transport.codec {
+        VideoCodec::H264 => "H264",
+    };
+    gstreamer::Caps::builder("application/x-rtp")
+        .field("media", "video")
+        .field("clock-rate", 90_000)
+        .field("encoding-name", encoding_name)
+        .field("payload", i32::from(transport.payload_type))
+        .field("ssrc", transport.ssrc)
+        .build()
+}
+
+/// Never resolves without a socket, when only watching
+async fn readable(socket: Option<&UdpSocket>) -> std::io::Result<()> {
+    match socket {
+        Some(socket) => socket.readable().await,
+        None => std::future::pending().await,
+    }
+}
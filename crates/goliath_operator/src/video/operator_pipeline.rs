@@ -1,3 +1,4 @@
+use crate::config::VideoConfig;
 use crate::error::GoliathOperatorResult;
 use goliath_common::{GoliathGstPipeline, GoliathVideoError, PipelineWrapper};
 use gstreamer::ClockTime;
@@ -10,7 +11,10 @@ pub(crate) struct OperatorPipeline {
 }
 
 impl OperatorPipeline {
-    pub(crate) fn try_new(rtp_caps: gstreamer::Caps) -> GoliathOperatorResult<Self> {
+    pub(crate) fn try_new(
+        rtp_caps: gstreamer::Caps,
+        config: &VideoConfig,
+    ) -> GoliathOperatorResult<Self> {
         let pipeline = gstreamer::Pipeline::builder()
             .name("CapturePipeline")
             .async_handling(false)
@@ -19,7 +23,8 @@ impl OperatorPipeline {
 
         let src = gstreamer::ElementFactory::make("udpsrc")
             .name("udp_source")
-            .property("port", 9000)
+            .property("address", "::") // Dual-stack, the vehicle may be reached over IPv6
+            .property("port", i32::from(config.port))
             .build()?;
 
         let capsfilter = gstreamer::ElementFactory::make("capsfilter")
@@ -49,7 +54,7 @@ impl OperatorPipeline {
             .name("format_converter")
             .build()?;
 
-        let ximagesink = gstreamer::ElementFactory::make("ximagesink")
+        let video_sink = gstreamer::ElementFactory::make(config.sink.element_name())
             .name("video_window")
             .property("sync", false)
             .property("async", false)
@@ -63,7 +68,7 @@ impl OperatorPipeline {
             &decoder,
             &queue,
             &videoconvert,
-            &ximagesink,
+            &video_sink,
         ])?;
 
         src.link(&capsfilter)?;
@@ -72,7 +77,7 @@ impl OperatorPipeline {
         h264parse.link(&decoder)?;
         decoder.link(&queue)?;
         queue.link(&videoconvert)?;
-        videoconvert.link(&ximagesink)?;
+        videoconvert.link(&video_sink)?;
 
         Ok(Self {
             pipeline: PipelineWrapper::wrap(pipeline),
@@ -0,0 +1,83 @@
+use crate::config::{DEFAULT_CONFIG_PATH, VehicleConfig};
+use crate::error::GoliathVehicleResult;
+use crate::video::capture_pipeline::ZedCamCaps;
+use crate::video::encoding_pipeline::EncoderType;
+use std::num::ParseIntError;
+use std::path::PathBuf;
+
+/// Runs the vehicle, every option overrides its value from the config file
+#[derive(Debug, clap::Parser)]
+#[command(version, about)]
+pub(crate) struct Cli {
+    /// TOML config, the defaults are used if it does not exist
+    #[arg(short, long, env = "GOLIATH_VEHICLE_CONFIG", default_value = DEFAULT_CONFIG_PATH)]
+    config: PathBuf,
+
+    /// Port of the control websocket
+    #[arg(long, env = "GOLIATH_VEHICLE_PORT")]
+    port: Option<u16>,
+
+    /// V4L2 device of the camera
+    #[arg(long, env = "GOLIATH_VEHICLE_VIDEO_DEVICE")]
+    video_device: Option<String>,
+
+    #[arg(long, env = "GOLIATH_VEHICLE_CAPTURE_CAPS", value_enum)]
+    capture_caps: Option<ZedCamCaps>,
+
+    #[arg(long, env = "GOLIATH_VEHICLE_ENCODER", value_enum)]
+    encoder: Option<EncoderType>,
+
+    /// RTP payload type of the video, in the dynamic range
+    #[arg(long, env = "GOLIATH_VEHICLE_PAYLOAD_TYPE")]
+    payload_type: Option<u8>,
+
+    /// I2C bus of the display
+    #[arg(long, env = "GOLIATH_VEHICLE_I2C_BUS")]
+    i2c_bus: Option<u8>,
+
+    /// I2C address of the display, decimal or 0x prefixed hex
+    #[arg(long, env = "GOLIATH_VEHICLE_DISPLAY_ADDRESS", value_parser = parse_address)]
+    display_address: Option<u8>,
+}
+
+impl Cli {
+    /// Loads the config file, applies the overrides and validates the result
+    pub(crate) fn load_config(&self) -> GoliathVehicleResult<VehicleConfig> {
+        let mut config = VehicleConfig::load(&self.config)?;
+
+        if let Some(port) = self.port {
+            config.server.port = port;
+        }
+        if let Some(device) = &self.video_device {
+            config.video.device = device.clone();
+        }
+        if let Some(capture_caps) = self.capture_caps {
+            config.video.capture_caps = capture_caps;
+        }
+        if let Some(encoder) = self.encoder {
+            config.video.encoder = encoder;
+        }
+        if let Some(payload_type) = self.payload_type {
+            config.video.payload_type = payload_type;
+        }
+        if let Some(i2c_bus) = self.i2c_bus {
+            config.display.i2c_bus = i2c_bus;
+        }
+        if let Some(address) = self.display_address {
+            config.display.address = address;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn parse_address(value: &str) -> Result<u8, ParseIntError> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u8::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+}
@@ -0,0 +1,135 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+use std::path::Path;
+
+// Tokens shorter than this are too easy to guess
+const MIN_TOKEN_LEN: usize = 16;
+
+/// Operators must authenticate with one of these before a session is created.
+/// If none is set any operator is accepted with the role it asks for
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct AuthConfig {
+    pub(crate) driver_token: Option<String>,
+    pub(crate) observer_token: Option<String>,
+    pub(crate) safety_token: Option<String>,
+    // One `<role> <hex ed25519 public key> [comment]` per line, `#` starts a comment
+    pub(crate) authorized_keys_path: Option<String>,
+}
+
+impl AuthConfig {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.driver_token.is_some()
+            || self.observer_token.is_some()
+            || self.safety_token.is_some()
+            || self.authorized_keys_path.is_some()
+    }
+
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        let tokens = [
+            ("driver", &self.driver_token),
+            ("observer", &self.observer_token),
+            ("safety", &self.safety_token),
+        ];
+        for (idx, (role, token)) in tokens.iter().enumerate() {
+            let Some(token) = token else {
+                continue;
+            };
+            if token.len() < MIN_TOKEN_LEN {
+                return Err(GoliathVehicleError::ConfigError(format!(
+                    "Auth tokens must be at least {MIN_TOKEN_LEN} characters, the {role} one isn't"
+                )));
+            }
+            // A shared token would grant the highest of the roles
+            if let Some((other_role, _)) = tokens[..idx]
+                .iter()
+                .find(|(_, other)| other.as_ref() == Some(token))
+            {
+                return Err(GoliathVehicleError::ConfigError(format!(
+                    "The {other_role} and {role} tokens must differ"
+                )));
+            }
+        }
+        if let Some(path) = &self.authorized_keys_path
+            && !Path::new(path).is_file()
+        {
+            return Err(GoliathVehicleError::ConfigError(format!(
+                "Authorized keys file {path} does not exist"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRIVER_TOKEN: &str = "driver-token-0123456789";
+    const OBSERVER_TOKEN: &str = "observer-token-0123456789";
+    const SAFETY_TOKEN: &str = "safety-token-0123456789";
+
+    fn config() -> AuthConfig {
+        AuthConfig {
+            driver_token: Some(DRIVER_TOKEN.to_string()),
+            observer_token: Some(OBSERVER_TOKEN.to_string()),
+            safety_token: Some(SAFETY_TOKEN.to_string()),
+            authorized_keys_path: None,
+        }
+    }
+
+    #[test]
+    fn distinct_long_tokens_are_valid() {
+        config().validate().unwrap();
+        AuthConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_short_tokens() {
+        let short = Some("s".repeat(MIN_TOKEN_LEN - 1));
+        let configs = [
+            AuthConfig {
+                driver_token: short.clone(),
+                ..config()
+            },
+            AuthConfig {
+                observer_token: short.clone(),
+                ..config()
+            },
+            AuthConfig {
+                safety_token: short,
+                ..config()
+            },
+        ];
+        for config in configs {
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+
+    #[test]
+    fn rejects_shared_tokens() {
+        let configs = [
+            AuthConfig {
+                observer_token: Some(DRIVER_TOKEN.to_string()),
+                ..config()
+            },
+            AuthConfig {
+                safety_token: Some(OBSERVER_TOKEN.to_string()),
+                ..config()
+            },
+            AuthConfig {
+                safety_token: Some(DRIVER_TOKEN.to_string()),
+                ..config()
+            },
+            // Without a driver token in between
+            AuthConfig {
+                driver_token: None,
+                observer_token: Some(SAFETY_TOKEN.to_string()),
+                ..config()
+            },
+        ];
+        for config in configs {
+            assert!(config.validate().is_err(), "{config:?}");
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+
+/// What to do with a command value that is outside its valid range
+#[derive(Copy, Clone, Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SanitizePolicy {
+    #[default]
+    Reject,
+    // Non-finite values are rejected regardless, there is no sensible value to clamp them to
+    Clamp,
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct CommandsConfig {
+    pub(crate) thrust: SanitizePolicy,
+    pub(crate) steer: SanitizePolicy,
+    pub(crate) track_power: SanitizePolicy,
+    pub(crate) turret_angle: SanitizePolicy,
+    pub(crate) turret_rate: SanitizePolicy,
+    // Control commands arriving later than this, relative to the fastest one seen, are dropped
+    pub(crate) max_age_ms: u64,
+    // The driving lease is freed after this long without a command from its holder
+    pub(crate) lease_timeout_ms: u64,
+}
+
+impl Default for CommandsConfig {
+    fn default() -> Self {
+        Self {
+            thrust: SanitizePolicy::default(),
+            steer: SanitizePolicy::default(),
+            track_power: SanitizePolicy::default(),
+            turret_angle: SanitizePolicy::default(),
+            turret_rate: SanitizePolicy::default(),
+            max_age_ms: 300,
+            lease_timeout_ms: 3000,
+        }
+    }
+}
+
+impl CommandsConfig {
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        if self.max_age_ms == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Max command age must be positive".to_string(),
+            ));
+        }
+        if self.lease_timeout_ms == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Lease timeout must be positive".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
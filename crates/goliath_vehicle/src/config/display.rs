@@ -0,0 +1,70 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+
+// The only bus the Orin exposes
+const I2C_BUSES: [u8; 1] = [1];
+
+/// The SSD1306 screen, only used by the jetson build
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct DisplayConfig {
+    pub(crate) i2c_bus: u8,
+    pub(crate) address: u8, // 7 bit
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            i2c_bus: 1,
+            address: 0x3C,
+        }
+    }
+}
+
+impl DisplayConfig {
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        if !I2C_BUSES.contains(&self.i2c_bus) {
+            return Err(GoliathVehicleError::ConfigError(format!(
+                "I2C bus {} is not available, the display must be on one of {I2C_BUSES:?}",
+                self.i2c_bus
+            )));
+        }
+        if self.address > 0x7F {
+            return Err(GoliathVehicleError::ConfigError(format!(
+                "Display address {:#04x} is not a 7 bit I2C address",
+                self.address
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        DisplayConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_unavailable_buses() {
+        for i2c_bus in [0, 2, 7] {
+            let config = DisplayConfig {
+                i2c_bus,
+                ..DisplayConfig::default()
+            };
+            assert!(config.validate().is_err(), "bus {i2c_bus}");
+        }
+    }
+
+    #[test]
+    fn rejects_addresses_over_7_bits() {
+        let config = DisplayConfig {
+            address: 0x80,
+            ..DisplayConfig::default()
+        };
+        assert!(config.validate().is_err());
+    }
+}
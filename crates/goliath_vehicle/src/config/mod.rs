@@ -0,0 +1,68 @@
+use crate::error::GoliathVehicleResult;
+use std::path::Path;
+
+mod auth;
+mod commands;
+mod display;
+mod motors;
+mod server;
+mod telemetry;
+mod tls;
+mod video;
+
+pub(crate) use auth::AuthConfig;
+pub(crate) use commands::{CommandsConfig, SanitizePolicy};
+pub(crate) use display::DisplayConfig;
+pub(crate) use motors::{MotorsConfig, RampingConfig, TrackWiring, TurretConfig};
+pub(crate) use server::ServerConfig;
+pub(crate) use telemetry::TelemetryConfig;
+pub(crate) use tls::TlsConfig;
+pub(crate) use video::VideoConfig;
+
+pub(crate) const DEFAULT_CONFIG_PATH: &str = "goliath_vehicle.toml";
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct VehicleConfig {
+    pub(crate) server: ServerConfig,
+    pub(crate) motors: MotorsConfig,
+    pub(crate) commands: CommandsConfig,
+    pub(crate) telemetry: TelemetryConfig,
+    pub(crate) video: VideoConfig,
+    pub(crate) display: DisplayConfig,
+    pub(crate) auth: AuthConfig,
+    // Plain websocket if absent
+    pub(crate) tls: Option<TlsConfig>,
+}
+
+impl VehicleConfig {
+    /// Loads the config, falling back to the defaults if the file does not exist. Left to the
+    /// caller to validate, once any overrides are applied
+    pub(crate) fn load(path: impl AsRef<Path>) -> GoliathVehicleResult<Self> {
+        let path = path.as_ref();
+        let config = if path.exists() {
+            log::info!("Loading config from {}", path.display());
+            toml::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            log::warn!("No config found at {}, using defaults", path.display());
+            Self::default()
+        };
+
+        Ok(config)
+    }
+
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        self.server.validate()?;
+        self.motors.validate()?;
+        self.commands.validate()?;
+        self.telemetry.validate()?;
+        self.video.validate()?;
+        self.display.validate()?;
+        self.auth.validate()?;
+        if let Some(tls) = &self.tls {
+            tls.validate()?;
+        }
+
+        Ok(())
+    }
+}
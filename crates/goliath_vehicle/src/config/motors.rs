@@ -0,0 +1,268 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+use crate::motors::validate_calibration;
+use goliath_common::{DriveMode, TrackCalibration};
+
+// The only header pins with a hardware PWM, any other fails when the backend claims it
+const PWM_PINS: [u8; 3] = [15, 32, 33];
+
+/// Physical pins a single track's motor driver is wired to
+#[derive(Copy, Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TrackWiring {
+    pub(crate) pwm_pin: u8,
+    pub(crate) forward_pin: u8,
+    pub(crate) backward_pin: u8,
+    // For motors that are mounted or wired in reverse
+    #[serde(default)]
+    pub(crate) inverted: bool,
+}
+
+/// Limits on how fast the power of each track may change, in full power per second
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct RampingConfig {
+    pub(crate) max_acceleration: f32,
+    pub(crate) max_deceleration: f32,
+    // Time to stay stopped before changing a track's direction
+    pub(crate) reversal_dwell_ms: u64,
+    // Interval at which the motors thread updates the tracks
+    pub(crate) tick_ms: u64,
+}
+
+impl Default for RampingConfig {
+    fn default() -> Self {
+        Self {
+            max_acceleration: 2.0,
+            max_deceleration: 4.0,
+            reversal_dwell_ms: 150,
+            tick_ms: 20,
+        }
+    }
+}
+
+impl RampingConfig {
+    fn validate(&self) -> GoliathVehicleResult<()> {
+        let is_positive = |rate: f32| rate.is_finite() && rate > 0.0;
+        if !is_positive(self.max_acceleration) || !is_positive(self.max_deceleration) {
+            return Err(GoliathVehicleError::ConfigError(
+                "Motors acceleration and deceleration limits must be positive".to_string(),
+            ));
+        }
+
+        if self.tick_ms == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Motors tick interval must be positive".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// Servo rotating the turret, angles are in degrees with 0 facing forward
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct TurretConfig {
+    pub(crate) pwm_pin: u8,
+    pub(crate) frequency_hz: u32,
+    pub(crate) min_angle: f32,
+    pub(crate) max_angle: f32,
+    // Pulse widths matching the minimum and maximum angles
+    pub(crate) min_pulse_us: u32,
+    pub(crate) max_pulse_us: u32,
+    // Degrees per second, for both angle and rate commands
+    pub(crate) max_speed: f32,
+    pub(crate) home_angle: f32,
+}
+
+impl Default for TurretConfig {
+    fn default() -> Self {
+        Self {
+            pwm_pin: 15,
+            frequency_hz: 50,
+            min_angle: -90.0,
+            max_angle: 90.0,
+            min_pulse_us: 1000,
+            max_pulse_us: 2000,
+            max_speed: 90.0,
+            home_angle: 0.0,
+        }
+    }
+}
+
+impl TurretConfig {
+    fn validate(&self) -> GoliathVehicleResult<()> {
+        if !(self.min_angle.is_finite() && self.max_angle.is_finite())
+            || self.min_angle >= self.max_angle
+        {
+            return Err(GoliathVehicleError::ConfigError(
+                "Turret angle range must be finite and non-empty".to_string(),
+            ));
+        }
+
+        if !(self.min_angle..=self.max_angle).contains(&self.home_angle) {
+            return Err(GoliathVehicleError::ConfigError(
+                "Turret home angle must be within its angle range".to_string(),
+            ));
+        }
+
+        if !(self.max_speed.is_finite() && self.max_speed > 0.0) {
+            return Err(GoliathVehicleError::ConfigError(
+                "Turret max speed must be positive".to_string(),
+            ));
+        }
+
+        if self.frequency_hz == 0 || self.min_pulse_us >= self.max_pulse_us {
+            return Err(GoliathVehicleError::ConfigError(
+                "Turret frequency must be positive, and pulse range non-empty".to_string(),
+            ));
+        }
+
+        // The pulse has to fit within a single period
+        let period_us = 1_000_000 / self.frequency_hz;
+        if self.max_pulse_us > period_us {
+            return Err(GoliathVehicleError::ConfigError(format!(
+                "Turret max pulse of {}us is longer than the PWM period of {period_us}us",
+                self.max_pulse_us
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct TracksCalibration {
+    pub(crate) left: TrackCalibration,
+    pub(crate) right: TrackCalibration,
+}
+
+impl TracksCalibration {
+    fn validate(&self) -> GoliathVehicleResult<()> {
+        validate_calibration(&self.left).map_err(|err| {
+            GoliathVehicleError::ConfigError(format!("Left track calibration: {err}"))
+        })?;
+        validate_calibration(&self.right).map_err(|err| {
+            GoliathVehicleError::ConfigError(format!("Right track calibration: {err}"))
+        })
+    }
+}
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct MotorsConfig {
+    pub(crate) left_track: TrackWiring,
+    pub(crate) right_track: TrackWiring,
+    // Tracks are stopped if no command (including heartbeats) arrives for this long
+    pub(crate) watchdog_timeout_ms: u64,
+    // Mode the tracks start in, the operator can switch it at runtime
+    pub(crate) drive_mode: DriveMode,
+    pub(crate) calibration: TracksCalibration,
+    pub(crate) ramping: RampingConfig,
+    pub(crate) turret: TurretConfig,
+}
+
+impl Default for MotorsConfig {
+    fn default() -> Self {
+        Self {
+            left_track: TrackWiring {
+                pwm_pin: 32,
+                forward_pin: 3,
+                backward_pin: 4,
+                inverted: false,
+            },
+            right_track: TrackWiring {
+                pwm_pin: 33,
+                forward_pin: 35,
+                backward_pin: 37,
+                inverted: false,
+            },
+            watchdog_timeout_ms: 500,
+            drive_mode: DriveMode::Arcade,
+            calibration: TracksCalibration::default(),
+            ramping: RampingConfig::default(),
+            turret: TurretConfig::default(),
+        }
+    }
+}
+
+impl MotorsConfig {
+    /// Checks the values, and makes sure no pin is used for more than one role before any of
+    /// them is claimed
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        if self.watchdog_timeout_ms == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Motors watchdog timeout must be positive".to_string(),
+            ));
+        }
+        self.ramping.validate()?;
+        self.calibration.validate()?;
+        self.turret.validate()?;
+
+        let pwm_roles = [
+            ("left track PWM", self.left_track.pwm_pin),
+            ("right track PWM", self.right_track.pwm_pin),
+            ("turret PWM", self.turret.pwm_pin),
+        ];
+        if let Some((role, pin)) = pwm_roles.iter().find(|(_, pin)| !PWM_PINS.contains(pin)) {
+            return Err(GoliathVehicleError::ConfigError(format!(
+                "Pin {pin} has no PWM, the {role} pin must be one of {PWM_PINS:?}"
+            )));
+        }
+
+        let roles = [
+            ("left track PWM", self.left_track.pwm_pin),
+            ("left track forward", self.left_track.forward_pin),
+            ("left track backward", self.left_track.backward_pin),
+            ("right track PWM", self.right_track.pwm_pin),
+            ("right track forward", self.right_track.forward_pin),
+            ("right track backward", self.right_track.backward_pin),
+            ("turret PWM", self.turret.pwm_pin),
+        ];
+
+        roles.iter().enumerate().try_for_each(|(idx, (role, pin))| {
+            match roles[idx + 1..]
+                .iter()
+                .find(|(_, other_pin)| other_pin == pin)
+            {
+                Some((other_role, _)) => Err(GoliathVehicleError::PinConflict {
+                    pin: *pin,
+                    first_role: role.to_string(),
+                    second_role: other_role.to_string(),
+                }),
+                None => Ok(()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        MotorsConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_pwm_on_a_plain_output() {
+        let mut config = MotorsConfig::default();
+        config.right_track.pwm_pin = 31;
+        assert!(matches!(
+            config.validate(),
+            Err(GoliathVehicleError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_shared_pins() {
+        let mut config = MotorsConfig::default();
+        config.right_track.backward_pin = config.left_track.forward_pin;
+        assert!(matches!(
+            config.validate(),
+            Err(GoliathVehicleError::PinConflict { pin: 3, .. })
+        ));
+    }
+}
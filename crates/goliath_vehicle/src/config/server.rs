@@ -0,0 +1,40 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct ServerConfig {
+    // Of the control websocket, on both IPv6 and IPv4
+    pub(crate) port: u16,
+    // Operators connected at once, each one is sent the video stream. A safety operator can
+    // still connect beyond it
+    pub(crate) max_connections: usize,
+    // A dropped operator's session is kept this long for it to resume, 0 disables resuming
+    pub(crate) resume_grace_ms: u64,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 5000,
+            max_connections: 4,
+            resume_grace_ms: 10_000,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        if self.port == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Server port must be positive".to_string(),
+            ));
+        }
+        if self.max_connections == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Max connections must be positive".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
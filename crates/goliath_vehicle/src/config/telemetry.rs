@@ -0,0 +1,38 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct TelemetryConfig {
+    // How often the session sends a telemetry report to the operator
+    pub(crate) interval_ms: u64,
+    // How often the link to the operator is measured
+    pub(crate) ping_interval_ms: u64,
+    pub(crate) cpu_temperature_path: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            interval_ms: 1000,
+            ping_interval_ms: 1000,
+            cpu_temperature_path: "/sys/class/thermal/thermal_zone0/temp".to_string(),
+        }
+    }
+}
+
+impl TelemetryConfig {
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        if self.interval_ms == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Telemetry interval must be positive".to_string(),
+            ));
+        }
+        if self.ping_interval_ms == 0 {
+            return Err(GoliathVehicleError::ConfigError(
+                "Ping interval must be positive".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+use std::path::Path;
+
+/// Serves the control websocket over TLS when present in the config
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TlsConfig {
+    // PEM, the vehicle's certificate followed by any intermediates
+    pub(crate) cert_path: String,
+    pub(crate) key_path: String,
+    // PEM, if set only operators presenting a certificate signed by one of these CAs can connect
+    #[serde(default)]
+    pub(crate) client_ca_path: Option<String>,
+}
+
+impl TlsConfig {
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        let paths = [
+            Some(&self.cert_path),
+            Some(&self.key_path),
+            self.client_ca_path.as_ref(),
+        ];
+        for path in paths.into_iter().flatten() {
+            if !Path::new(path).is_file() {
+                return Err(GoliathVehicleError::ConfigError(format!(
+                    "TLS file {path} does not exist"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+use crate::video::capture_pipeline::ZedCamCaps;
+use crate::video::encoding_pipeline::EncoderType;
+use std::ops::RangeInclusive;
+
+// Not assigned to a codec by the RTP profile, free for the session to pick
+const DYNAMIC_PAYLOAD_TYPES: RangeInclusive<u8> = 96..=127;
+
+#[derive(Clone, Debug, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct VideoConfig {
+    pub(crate) device: String,
+    pub(crate) capture_caps: ZedCamCaps,
+    pub(crate) encoder: EncoderType,
+    // The operators announce the port they receive the stream on
+    pub(crate) payload_type: u8,
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            device: "/dev/video0".to_string(),
+            capture_caps: ZedCamCaps::NOHD15,
+            encoder: EncoderType::V4L2,
+            payload_type: 96,
+        }
+    }
+}
+
+impl VideoConfig {
+    pub(crate) fn validate(&self) -> GoliathVehicleResult<()> {
+        if self.device.is_empty() {
+            return Err(GoliathVehicleError::ConfigError(
+                "Video device must be set".to_string(),
+            ));
+        }
+        if !DYNAMIC_PAYLOAD_TYPES.contains(&self.payload_type) {
+            return Err(GoliathVehicleError::ConfigError(format!(
+                "RTP payload type must be in the dynamic range {DYNAMIC_PAYLOAD_TYPES:?}"
+            )));
+        }
+
+        Ok(())
+    }
+}
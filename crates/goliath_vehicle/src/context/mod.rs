@@ -0,0 +1,17 @@
+use crate::config::VehicleConfig;
+use crate::emergency_stop::EmergencyStop;
+use crate::lease::DrivingLease;
+use crate::motors::MotorsHandle;
+use crate::session::ParkedSessions;
+use crate::video::streamer::VideoStreamer;
+use std::sync::Arc;
+
+/// Vehicle wide state, outliving the sessions it is shared with
+pub(crate) struct VehicleContext {
+    pub(crate) config: VehicleConfig,
+    pub(crate) emergency_stop: Arc<EmergencyStop>,
+    pub(crate) lease: DrivingLease,
+    pub(crate) motors: MotorsHandle,
+    pub(crate) video: VideoStreamer,
+    pub(crate) parked_sessions: ParkedSessions,
+}
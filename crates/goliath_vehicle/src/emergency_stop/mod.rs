@@ -0,0 +1,35 @@
+use std::mem;
+use tokio::sync::watch;
+
+/// Vehicle wide emergency stop, latched across sessions until it is explicitly cleared
+pub(crate) struct EmergencyStop {
+    engaged: watch::Sender<bool>,
+}
+
+impl EmergencyStop {
+    pub(crate) fn new() -> Self {
+        Self {
+            engaged: watch::Sender::new(false),
+        }
+    }
+
+    /// Returns false if it was already engaged
+    pub(crate) fn engage(&self) -> bool {
+        self.engaged
+            .send_if_modified(|engaged| !mem::replace(engaged, true))
+    }
+
+    /// Returns false if it was not engaged
+    pub(crate) fn clear(&self) -> bool {
+        self.engaged
+            .send_if_modified(|engaged| mem::replace(engaged, false))
+    }
+
+    pub(crate) fn is_engaged(&self) -> bool {
+        *self.engaged.borrow()
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<bool> {
+        self.engaged.subscribe()
+    }
+}
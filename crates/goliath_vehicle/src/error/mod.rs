@@ -1,4 +1,4 @@
-use goliath_common::{GoliathTracingError, GoliathVideoError};
+use goliath_common::{GoliathSerdeError, GoliathTracingError, GoliathVideoError};
 use gstreamer::glib;
 
 pub(crate) type GoliathVehicleResult<T> = Result<T, GoliathVehicleError>;
@@ -9,9 +9,11 @@ pub(crate) enum GoliathVehicleError {
     #[error("General error: {0}")]
     GeneralError(String),
 
+    #[cfg(feature = "jetson")]
     #[error("GPIO error: {0}")]
     GPIOError(#[from] jetgpio::gpio::Error),
 
+    #[cfg(feature = "jetson")]
     #[error("I2C error: {0}")]
     I2CError(#[from] jetgpio::i2c::Error),
 
@@ -45,6 +47,34 @@ pub(crate) enum GoliathVehicleError {
     #[error("Video pipeline error: {0}")]
     VideoError(#[from] GoliathVideoError),
 
+    #[error("Error while serializing/deserializing: {0}")]
+    SerdeError(#[from] GoliathSerdeError),
+
     #[error("Error while initializing logging/tracing: {0}")]
     TracingInitError(#[from] GoliathTracingError),
+
+    #[error("Handshake error: {0}")]
+    HandshakeError(String),
+
+    #[error("Authentication error: {0}")]
+    AuthenticationError(String),
+
+    #[error("TLS error: {0}")]
+    TlsError(#[from] rustls::Error),
+
+    #[error("Error reading PEM file: {0}")]
+    PemError(#[from] rustls_pki_types::pem::Error),
+
+    #[error("Config error: {0}")]
+    ConfigError(String),
+
+    #[error("Config parsing error: {0}")]
+    ConfigParseError(#[from] toml::de::Error),
+
+    #[error("Pin {pin} is assigned to both {first_role} and {second_role}")]
+    PinConflict {
+        pin: u8,
+        first_role: String,
+        second_role: String,
+    },
 }
@@ -0,0 +1,258 @@
+use goliath_common::Role;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+#[derive(Clone, Debug)]
+pub(crate) struct LeaseHolder {
+    pub(crate) session_id: u64,
+    pub(crate) label: String, // Describes the holder to the other operators
+    pub(crate) role: Role,
+    expires: Instant,
+}
+
+/// Vehicle wide right to drive, held by at most one session at a time
+pub(crate) struct DrivingLease {
+    holder: watch::Sender<Option<LeaseHolder>>,
+    timeout: Duration, // Without a command from the holder
+}
+
+impl DrivingLease {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            holder: watch::Sender::new(None),
+            timeout,
+        }
+    }
+
+    /// Granted if the lease is free, stale or already held by the session, and to a safety
+    /// operator over anyone who isn't one. Otherwise returns who holds it
+    pub(crate) fn take(&self, session_id: u64, label: &str, role: Role) -> Result<(), String> {
+        let mut result = Ok(());
+        self.holder.send_if_modified(|holder| {
+            match holder {
+                Some(current) if current.session_id == session_id => {
+                    current.expires = Instant::now() + self.timeout;
+                    return false;
+                }
+                Some(current)
+                    if current.expires > Instant::now()
+                        && (role != Role::Safety || current.role == Role::Safety) =>
+                {
+                    result = Err(current.label.clone());
+                    return false;
+                }
+                Some(current) => {
+                    log::warn!("{label} took the driving lease over from {}", current.label);
+                }
+                None => log::info!("{label} took the driving lease"),
+            }
+
+            *holder = Some(LeaseHolder {
+                session_id,
+                label: label.to_string(),
+                role,
+                expires: Instant::now() + self.timeout,
+            });
+            true
+        });
+        result
+    }
+
+    /// Returns false if the session didn't hold the lease
+    pub(crate) fn release(&self, session_id: u64) -> bool {
+        self.holder.send_if_modified(|holder| {
+            let held = holder
+                .as_ref()
+                .is_some_and(|current| current.session_id == session_id);
+            if held {
+                log::info!("Driving lease released");
+                *holder = None;
+            }
+            held
+        })
+    }
+
+    /// Keeps the lease from expiring, returns false if the session doesn't hold it
+    pub(crate) fn renew(&self, session_id: u64) -> bool {
+        let mut held = false;
+        // Nobody needs to know about a renewal
+        self.holder.send_if_modified(|holder| {
+            if let Some(current) = holder
+                && current.session_id == session_id
+            {
+                current.expires = Instant::now() + self.timeout;
+                held = true;
+            }
+            false
+        });
+        held
+    }
+
+    /// Keeps the lease for a disconnected holder that may come back, for `grace` instead of
+    /// the usual timeout
+    pub(crate) fn park(&self, session_id: u64, grace: Duration) {
+        self.holder.send_if_modified(|holder| {
+            if let Some(current) = holder
+                && current.session_id == session_id
+            {
+                current.expires = Instant::now() + grace;
+            }
+            false
+        });
+    }
+
+    pub(crate) fn is_held_by(&self, session_id: u64) -> bool {
+        self.holder
+            .borrow()
+            .as_ref()
+            .is_some_and(|current| current.session_id == session_id)
+    }
+
+    pub(crate) fn subscribe(&self) -> watch::Receiver<Option<LeaseHolder>> {
+        self.holder.subscribe()
+    }
+
+    /// Frees the lease whenever its holder stops sending commands, never returns
+    pub(crate) async fn run_expiry(&self) {
+        let mut interval = tokio::time::interval(self.timeout / 4);
+        loop {
+            interval.tick().await;
+            self.holder.send_if_modified(|holder| match holder {
+                Some(current) if current.expires <= Instant::now() => {
+                    log::warn!("Driving lease of {} expired", current.label);
+                    *holder = None;
+                    true
+                }
+                _ => false,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const TIMEOUT: Duration = Duration::from_millis(100);
+
+    fn held() -> DrivingLease {
+        let lease = DrivingLease::new(TIMEOUT);
+        lease.take(1, "first", Role::Driver).unwrap();
+        lease
+    }
+
+    #[test]
+    fn free_lease_is_granted() {
+        let lease = DrivingLease::new(TIMEOUT);
+        let mut holder_rx = lease.subscribe();
+        assert_eq!(lease.take(1, "first", Role::Driver), Ok(()));
+        assert!(lease.is_held_by(1));
+        assert!(!lease.is_held_by(2));
+        assert!(holder_rx.has_changed().unwrap());
+        assert_eq!(
+            holder_rx.borrow_and_update().as_ref().unwrap().label,
+            "first"
+        );
+
+        // Taking it again only renews it
+        assert_eq!(lease.take(1, "first", Role::Driver), Ok(()));
+        assert!(!holder_rx.has_changed().unwrap());
+    }
+
+    #[test]
+    fn held_lease_is_refused_to_others() {
+        let lease = held();
+        assert_eq!(
+            lease.take(2, "second", Role::Driver),
+            Err("first".to_string())
+        );
+        assert_eq!(
+            lease.take(2, "second", Role::Observer),
+            Err("first".to_string())
+        );
+        assert!(lease.is_held_by(1));
+    }
+
+    #[test]
+    fn renewal_keeps_the_lease() {
+        let lease = held();
+        assert!(!lease.renew(2));
+        for _ in 0..3 {
+            std::thread::sleep(TIMEOUT / 2);
+            assert!(lease.renew(1));
+        }
+        assert!(lease.take(2, "second", Role::Driver).is_err());
+    }
+
+    #[test]
+    fn released_lease_is_free() {
+        let lease = held();
+        assert!(!lease.release(2));
+        assert!(lease.is_held_by(1));
+
+        assert!(lease.release(1));
+        assert!(!lease.is_held_by(1));
+        assert!(!lease.release(1));
+        assert_eq!(lease.take(2, "second", Role::Driver), Ok(()));
+    }
+
+    #[test]
+    fn expired_lease_can_be_taken() {
+        let lease = held();
+        std::thread::sleep(TIMEOUT * 2);
+        assert!(!lease.renew(2));
+        assert_eq!(lease.take(2, "second", Role::Driver), Ok(()));
+        assert!(lease.is_held_by(2));
+        assert!(!lease.renew(1));
+    }
+
+    #[test]
+    fn safety_takes_over_from_anyone_else() {
+        let lease = held();
+        assert_eq!(lease.take(2, "safety", Role::Safety), Ok(()));
+        assert!(lease.is_held_by(2));
+        assert_eq!(
+            lease.subscribe().borrow().as_ref().unwrap().role,
+            Role::Safety
+        );
+
+        // Not from another safety operator, nor back by the driver
+        assert_eq!(
+            lease.take(3, "other safety", Role::Safety),
+            Err("safety".to_string())
+        );
+        assert_eq!(
+            lease.take(1, "first", Role::Driver),
+            Err("safety".to_string())
+        );
+    }
+
+    #[test]
+    fn parked_lease_outlives_the_timeout() {
+        let lease = held();
+        lease.park(1, TIMEOUT * 4);
+        std::thread::sleep(TIMEOUT * 2);
+        assert!(lease.take(2, "second", Role::Driver).is_err());
+        assert!(lease.is_held_by(1));
+    }
+
+    #[tokio::test]
+    async fn expiry_frees_a_silent_holder() {
+        let lease = Arc::new(DrivingLease::new(TIMEOUT));
+        lease.take(1, "first", Role::Driver).unwrap();
+        let mut holder_rx = lease.subscribe();
+        holder_rx.mark_unchanged();
+
+        let expiry = tokio::spawn({
+            let lease = Arc::clone(&lease);
+            async move { lease.run_expiry().await }
+        });
+        tokio::time::timeout(TIMEOUT * 4, holder_rx.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(holder_rx.borrow().is_none());
+        expiry.abort();
+    }
+}
@@ -1,49 +1,115 @@
 #![allow(clippy::upper_case_acronyms)]
 #![deny(clippy::clone_on_ref_ptr)]
 
-use crate::image_proc::{convert_image_to_screen_space, load_goliath_logo, resize_image};
+use crate::cli::Cli;
+use crate::context::VehicleContext;
+use crate::emergency_stop::EmergencyStop;
+use crate::error::GoliathVehicleError;
+use crate::lease::DrivingLease;
+use crate::motors::MotorsContoller;
+use crate::motors::hal::MotorsBackend;
 use crate::server::GoliathServer;
-use crate::ssd1306::create_ssd_connection;
+use crate::session::ParkedSessions;
+use crate::video::streamer::VideoStreamer;
+use clap::Parser;
 use error::GoliathVehicleResult;
-use goliath_common::{initiate_gstreamer, start_main_loop};
-use jetgpio::Gpio;
+use goliath_common::{MotorCommand, initiate_gstreamer, start_main_loop, stop_main_loop};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::runtime::Handle;
 
+mod cli;
+mod config;
+mod context;
+mod emergency_stop;
 mod error;
+#[cfg(feature = "jetson")]
 mod image_proc;
+mod lease;
 mod motors;
 mod server;
 mod session;
+#[cfg(feature = "jetson")]
 mod ssd1306;
+mod telemetry;
 mod video;
 
 #[tokio::main(flavor = "multi_thread", worker_threads = 4)]
 async fn main() -> GoliathVehicleResult<()> {
+    let cli = Cli::parse();
     goliath_common::common_init_for_trace()?;
     initiate_gstreamer()?;
 
-    let gpio = Arc::new(Gpio::new()?);
-    let mut ssd = create_ssd_connection()?;
+    // Validate before touching any of the hardware
+    let config = cli.load_config()?;
 
-    let main_logo = load_goliath_logo().and_then(|img| {
-        convert_image_to_screen_space(
-            resize_image(img, ssd.width(), ssd.height()),
-            ssd.width(),
-            ssd.height(),
-        )
-    })?;
+    #[cfg(feature = "jetson")]
+    let motors_backend: Arc<dyn MotorsBackend> = Arc::new(motors::hal::JetsonBackend::try_new()?);
+    #[cfg(not(feature = "jetson"))]
+    let motors_backend: Arc<dyn MotorsBackend> = Arc::new(motors::hal::SimulatedBackend::new());
 
-    ssd.update_screen(0, &main_logo)?;
+    let emergency_stop = Arc::new(EmergencyStop::new());
 
-    let mut operator_connection = GoliathServer::try_new(5000).await?;
-    loop {
-        log::info!("Awaiting new connection");
-        let mut session_ctx = operator_connection
-            .await_connection(Arc::clone(&gpio))
-            .await?;
+    #[cfg(feature = "jetson")]
+    {
+        use crate::image_proc::{convert_image_to_screen_space, load_goliath_logo, resize_image};
 
-        Handle::current().spawn_blocking(start_main_loop);
-        tokio::spawn(async move { session_ctx.run().await }).await??;
+        let mut ssd = ssd1306::create_ssd_connection(&config.display)?;
+        let main_logo = load_goliath_logo().and_then(|img| {
+            convert_image_to_screen_space(
+                resize_image(img, ssd.width(), ssd.height()),
+                ssd.width(),
+                ssd.height(),
+            )
+        })?;
+
+        ssd.update_screen(0, &main_logo)?;
+
+        // The screen is inverted for as long as the emergency stop is latched
+        let mut emergency_stop_rx = emergency_stop.subscribe();
+        tokio::spawn(async move {
+            while emergency_stop_rx.changed().await.is_ok() {
+                let engaged = *emergency_stop_rx.borrow_and_update();
+                if let Err(err) = ssd.send_command(ssd1306::SSD1306Command::Inverse(engaged)) {
+                    log::error!("Failed to show emergency stop on screen: {err}");
+                }
+            }
+        });
     }
+
+    let (motors, motors_thread) =
+        MotorsContoller::try_new(motors_backend, Arc::clone(&emergency_stop), &config.motors)?
+            .spawn()?;
+    let video = VideoStreamer::try_new(&config.video)?;
+    let lease = DrivingLease::new(Duration::from_millis(config.commands.lease_timeout_ms));
+
+    let context = Arc::new(VehicleContext {
+        config,
+        emergency_stop,
+        lease,
+        motors,
+        video,
+        parked_sessions: ParkedSessions::new(),
+    });
+
+    tokio::spawn({
+        let context = Arc::clone(&context);
+        async move { context.lease.run_expiry().await }
+    });
+    Handle::current().spawn_blocking(start_main_loop);
+
+    let server = Arc::new(GoliathServer::try_new(&context.config).await?);
+    let result = tokio::select! {
+        result = server.run(Arc::clone(&context)) => result,
+        joined = tokio::task::spawn_blocking(move || motors_thread.join()) => match joined? {
+            Ok(result) => result,
+            Err(_) => Err(GoliathVehicleError::GeneralError("Motors thread panicked".to_string())),
+        },
+    };
+
+    // Whatever stopped the vehicle, nothing may keep moving
+    context.motors.cmd_tx.send(MotorCommand::End).await.ok();
+    context.video.stop();
+    stop_main_loop();
+    result
 }
@@ -0,0 +1,180 @@
+use goliath_common::{PowerCurve, TrackCalibration};
+
+// Far more than a motor's response needs, a longer lookup is only a cost to every command
+const MAX_LOOKUP_POINTS: usize = 64;
+
+/// Returns why the calibration can't be applied to a track, if it can't
+pub(crate) fn validate_calibration(calibration: &TrackCalibration) -> Result<(), String> {
+    if !(0.0..1.0).contains(&calibration.min_power) {
+        return Err(format!(
+            "Min power must be at least 0.0 and below 1.0, got {}",
+            calibration.min_power
+        ));
+    }
+
+    if !(calibration.trim.is_finite() && calibration.trim > 0.0) {
+        return Err(format!("Trim must be positive, got {}", calibration.trim));
+    }
+
+    match &calibration.curve {
+        PowerCurve::Linear => Ok(()),
+        PowerCurve::Exponential(exponent) if !(exponent.is_finite() && *exponent > 0.0) => Err(
+            format!("Power curve exponent must be positive, got {exponent}"),
+        ),
+        PowerCurve::Exponential(_) => Ok(()),
+        PowerCurve::Lookup(points) if points.len() < 2 => {
+            Err("Power curve lookup needs at least 2 points".to_string())
+        }
+        PowerCurve::Lookup(points) if points.len() > MAX_LOOKUP_POINTS => Err(format!(
+            "Power curve lookup has {} points, at most {MAX_LOOKUP_POINTS} are allowed",
+            points.len()
+        )),
+        PowerCurve::Lookup(points) if points.iter().any(|point| !(0.0..=1.0).contains(point)) => {
+            Err("Power curve lookup points must be between 0.0 and 1.0".to_string())
+        }
+        // A decreasing curve would slow the track down when asked to speed up
+        PowerCurve::Lookup(points) if points.windows(2).any(|pair| pair[1] < pair[0]) => {
+            Err("Power curve lookup points must not decrease".to_string())
+        }
+        PowerCurve::Lookup(_) => Ok(()),
+    }
+}
+
+/// Maps a requested power, between -1.0 and 1.0, to the power actually sent to the motor. The
+/// calibration applies to the magnitude, the direction is kept
+pub(crate) fn calibrated_power(calibration: &TrackCalibration, power: f32) -> f32 {
+    // The deadband only applies to a moving track, stopped has to stay stopped
+    if power == 0.0 || power.is_nan() {
+        return 0.0;
+    }
+    let sign = power.signum();
+    let power = power.abs().min(1.0);
+
+    let curved = match &calibration.curve {
+        PowerCurve::Linear => power,
+        PowerCurve::Exponential(exponent) => power.powf(*exponent),
+        PowerCurve::Lookup(points) => {
+            let position = power * (points.len() - 1) as f32;
+            let idx = (position.floor() as usize).min(points.len() - 2);
+            let fraction = position - idx as f32;
+            points[idx] + (points[idx + 1] - points[idx]) * fraction
+        }
+    };
+
+    let trimmed = (curved * calibration.trim).clamp(0.0, 1.0);
+    sign * (calibration.min_power + (1.0 - calibration.min_power) * trimmed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(len: usize) -> TrackCalibration {
+        TrackCalibration {
+            curve: PowerCurve::Lookup((0..len).map(|idx| idx as f32 / (len - 1) as f32).collect()),
+            ..TrackCalibration::default()
+        }
+    }
+
+    #[test]
+    fn lookup_length_is_bounded() {
+        assert!(validate_calibration(&lookup(1)).is_err());
+        assert!(validate_calibration(&lookup(2)).is_ok());
+        assert!(validate_calibration(&lookup(MAX_LOOKUP_POINTS)).is_ok());
+        assert!(validate_calibration(&lookup(MAX_LOOKUP_POINTS + 1)).is_err());
+    }
+
+    #[test]
+    fn lookup_interpolates_between_points() {
+        let calibration = TrackCalibration {
+            curve: PowerCurve::Lookup(vec![0.0, 0.5, 0.6]),
+            ..TrackCalibration::default()
+        };
+        assert_eq!(calibrated_power(&calibration, 0.0), 0.0);
+        assert_eq!(calibrated_power(&calibration, 0.25), 0.25);
+        assert_eq!(calibrated_power(&calibration, 0.5), 0.5);
+        assert_eq!(calibrated_power(&calibration, 1.0), 0.6);
+    }
+
+    #[test]
+    fn stopped_stays_stopped() {
+        let calibration = TrackCalibration {
+            min_power: 0.3,
+            trim: 1.5,
+            curve: PowerCurve::Exponential(2.0),
+        };
+        assert_eq!(calibrated_power(&calibration, 0.0), 0.0);
+        assert_eq!(calibrated_power(&calibration, -0.0), 0.0);
+    }
+
+    #[test]
+    fn moving_track_is_raised_above_the_deadband() {
+        let calibration = TrackCalibration {
+            min_power: 0.3,
+            ..TrackCalibration::default()
+        };
+        let forward = calibrated_power(&calibration, 0.01);
+        assert!((0.3..0.32).contains(&forward), "{forward}");
+        let backward = calibrated_power(&calibration, -0.01);
+        assert_eq!(backward, -forward);
+        assert_eq!(calibrated_power(&calibration, 1.0), 1.0);
+        assert_eq!(calibrated_power(&calibration, -1.0), -1.0);
+    }
+
+    #[test]
+    fn trim_is_clamped_to_full_power() {
+        let boosted = TrackCalibration {
+            trim: 1.5,
+            ..TrackCalibration::default()
+        };
+        assert_eq!(calibrated_power(&boosted, 0.5), 0.75);
+        assert_eq!(calibrated_power(&boosted, 0.9), 1.0);
+        assert_eq!(calibrated_power(&boosted, -0.9), -1.0);
+
+        let reduced = TrackCalibration {
+            trim: 0.5,
+            ..TrackCalibration::default()
+        };
+        assert_eq!(calibrated_power(&reduced, 1.0), 0.5);
+        assert_eq!(calibrated_power(&reduced, -1.0), -0.5);
+    }
+
+    #[test]
+    fn exponential_curve_keeps_the_endpoints_and_sign() {
+        let calibration = TrackCalibration {
+            curve: PowerCurve::Exponential(2.0),
+            ..TrackCalibration::default()
+        };
+        assert_eq!(calibrated_power(&calibration, 0.0), 0.0);
+        assert_eq!(calibrated_power(&calibration, 1.0), 1.0);
+        assert_eq!(calibrated_power(&calibration, -1.0), -1.0);
+        assert_eq!(calibrated_power(&calibration, 0.5), 0.25);
+        assert_eq!(calibrated_power(&calibration, -0.5), -0.25);
+    }
+
+    #[test]
+    fn rejects_invalid_min_power_and_trim() {
+        for min_power in [f32::NAN, f32::INFINITY, -0.1, 1.0, 1.5] {
+            let calibration = TrackCalibration {
+                min_power,
+                ..TrackCalibration::default()
+            };
+            assert!(validate_calibration(&calibration).is_err(), "{min_power}");
+        }
+        for trim in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, 0.0, -1.0] {
+            let calibration = TrackCalibration {
+                trim,
+                ..TrackCalibration::default()
+            };
+            assert!(validate_calibration(&calibration).is_err(), "{trim}");
+        }
+        for exponent in [f32::NAN, f32::INFINITY, 0.0, -2.0] {
+            let calibration = TrackCalibration {
+                curve: PowerCurve::Exponential(exponent),
+                ..TrackCalibration::default()
+            };
+            assert!(validate_calibration(&calibration).is_err(), "{exponent}");
+        }
+        validate_calibration(&TrackCalibration::default()).unwrap();
+    }
+}
@@ -1,23 +1,24 @@
 use crate::GoliathVehicleResult;
-use jetgpio::Pwm;
-use jetgpio::gpio::pins::OutputPin;
+use crate::config::TrackWiring;
+use crate::motors::hal::{DigitalOutput, MotorsBackend, PwmChannel};
 
 pub(crate) struct DirectionalMotorPin {
-    power: Pwm,
-    forward: OutputPin,
-    backward: OutputPin,
+    power: Box<dyn PwmChannel>,
+    forward: Box<dyn DigitalOutput>,
+    backward: Box<dyn DigitalOutput>,
+    inverted: bool,
 }
 
 impl DirectionalMotorPin {
     pub(crate) fn try_new(
-        power: Pwm,
-        forward: OutputPin,
-        backward: OutputPin,
+        backend: &dyn MotorsBackend,
+        wiring: TrackWiring,
     ) -> GoliathVehicleResult<Self> {
         Ok(Self {
-            power,
-            forward,
-            backward,
+            power: backend.get_pwm(wiring.pwm_pin)?,
+            forward: backend.get_output(wiring.forward_pin)?,
+            backward: backend.get_output(wiring.backward_pin)?,
+            inverted: wiring.inverted,
         })
     }
 
@@ -26,9 +27,9 @@ impl DirectionalMotorPin {
         power: f64,
         forward: bool,
     ) -> GoliathVehicleResult<()> {
-        assert!((0.0..=1.0).contains(&power));
+        let power = power.clamp(0.0, 1.0);
 
-        if forward {
+        if forward != self.inverted {
             self.forward.set_high()?;
             self.backward.set_low()?;
         } else {
@@ -0,0 +1,119 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+use crate::motors::hal::{DigitalOutput, MotorsBackend, PwmChannel};
+use jetgpio::gpio::pins::OutputPin;
+use jetgpio::gpio::valid_pins;
+use jetgpio::{Gpio, Pwm};
+use std::time::Duration;
+
+// The hardware duty cycle register goes from 0 to 256 over a full period
+const DUTY_CYCLE_RESOLUTION: f64 = 256.0;
+// Finer than this, a servo can be positioned well enough
+const MAX_SERVO_STEP: Duration = Duration::from_micros(20);
+
+// jetgpio represents every pin as its own type, so runtime pin numbers have to be matched by hand
+macro_rules! match_pin {
+    ($pin:expr, [$($number:literal => $pin_name:ident),*], |$valid_pin:ident| $func:expr) => {
+        match $pin {
+            $($number => {
+                let $valid_pin = valid_pins::$pin_name;
+                $func.map_err(GoliathVehicleError::from)
+            })*
+            _ => Err(GoliathVehicleError::GeneralError(format!(
+                "Pin {} can not be used for this role",
+                $pin
+            ))),
+        }
+    };
+}
+
+pub(crate) struct JetsonBackend {
+    gpio: Gpio,
+}
+
+impl JetsonBackend {
+    pub(crate) fn try_new() -> GoliathVehicleResult<Self> {
+        Ok(Self { gpio: Gpio::new()? })
+    }
+}
+
+impl MotorsBackend for JetsonBackend {
+    fn get_pwm(&self, pin: u8) -> GoliathVehicleResult<Box<dyn PwmChannel>> {
+        let pwm = match_pin!(
+            pin,
+            [15 => Pin15, 32 => Pin32, 33 => Pin33],
+            |valid_pin| Pwm::new(valid_pin)
+        )?;
+        Ok(Box::new(JetsonPwm { pwm, frequency: 0 }))
+    }
+
+    fn get_output(&self, pin: u8) -> GoliathVehicleResult<Box<dyn DigitalOutput>> {
+        let output = match_pin!(
+            pin,
+            [
+                3 => Pin3, 4 => Pin4, 5 => Pin5, 7 => Pin7, 8 => Pin8, 10 => Pin10, 11 => Pin11,
+                12 => Pin12, 14 => Pin14, 15 => Pin15, 16 => Pin16, 17 => Pin17, 18 => Pin18,
+                19 => Pin19, 21 => Pin21, 22 => Pin22, 23 => Pin23, 24 => Pin24, 26 => Pin26,
+                27 => Pin27, 28 => Pin28, 29 => Pin29, 31 => Pin31, 32 => Pin32, 33 => Pin33,
+                35 => Pin35, 36 => Pin36, 37 => Pin37, 38 => Pin38, 40 => Pin40
+            ],
+            |valid_pin| self.gpio.get_output(valid_pin)
+        )?;
+        Ok(Box::new(output))
+    }
+}
+
+struct JetsonPwm {
+    pwm: Pwm,
+    frequency: u32,
+}
+
+impl PwmChannel for JetsonPwm {
+    fn set_duty_cycle(&mut self, duty_cycle: u32) -> GoliathVehicleResult<()> {
+        self.set_period_fraction(f64::from(duty_cycle) / 100.0)
+    }
+
+    fn set_frequency(&mut self, frequency: u32) -> GoliathVehicleResult<()> {
+        self.pwm.set_frequency(frequency)?;
+        self.frequency = frequency;
+
+        // At the 50Hz servos use a step is 78us, so only about 13 positions fit between the
+        // usual 1ms and 2ms pulses
+        let step = Duration::from_secs_f64(1.0 / (f64::from(frequency) * DUTY_CYCLE_RESOLUTION));
+        if step > MAX_SERVO_STEP {
+            log::warn!("PWM at {frequency}Hz only has pulse width steps of {step:?}");
+        }
+        Ok(())
+    }
+
+    fn set_pulse_width(&mut self, pulse_width: Duration) -> GoliathVehicleResult<()> {
+        if self.frequency == 0 {
+            return Err(GoliathVehicleError::GeneralError(
+                "PWM frequency must be set before the pulse width".to_string(),
+            ));
+        }
+
+        self.set_period_fraction(pulse_width.as_secs_f64() * self.frequency as f64)
+    }
+}
+
+impl JetsonPwm {
+    /// Fraction of the period the output is high, scaled to the duty cycle register
+    fn set_period_fraction(&mut self, period_fraction: f64) -> GoliathVehicleResult<()> {
+        let duty_cycle = (period_fraction * DUTY_CYCLE_RESOLUTION)
+            .round()
+            .clamp(0.0, DUTY_CYCLE_RESOLUTION);
+        self.pwm
+            .set_duty_cycle(duty_cycle as u32)
+            .map_err(Into::into)
+    }
+}
+
+impl DigitalOutput for OutputPin {
+    fn set_high(&mut self) -> GoliathVehicleResult<()> {
+        OutputPin::set_high(self).map_err(Into::into)
+    }
+
+    fn set_low(&mut self) -> GoliathVehicleResult<()> {
+        OutputPin::set_low(self).map_err(Into::into)
+    }
+}
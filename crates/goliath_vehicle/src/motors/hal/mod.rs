@@ -0,0 +1,39 @@
+use crate::error::GoliathVehicleResult;
+use std::time::Duration;
+
+#[cfg(feature = "jetson")]
+mod jetson;
+// Also built for the tests, which run on the simulated outputs whatever the target
+#[cfg(any(test, not(feature = "jetson")))]
+mod simulated;
+
+#[cfg(feature = "jetson")]
+pub(crate) use jetson::JetsonBackend;
+#[cfg(test)]
+pub(crate) use simulated::OutputChange;
+#[cfg(any(test, not(feature = "jetson")))]
+pub(crate) use simulated::SimulatedBackend;
+
+/// A PWM channel, driving either the power of a motor or the position of a servo
+pub(crate) trait PwmChannel: Send {
+    /// Duty cycle is given in percent, 0 to 100
+    fn set_duty_cycle(&mut self, duty_cycle: u32) -> GoliathVehicleResult<()>;
+
+    fn set_frequency(&mut self, frequency: u32) -> GoliathVehicleResult<()>;
+
+    /// Converted to a duty cycle according to the last frequency set on the channel, so it is
+    /// rounded to the nearest step the channel can produce
+    fn set_pulse_width(&mut self, pulse_width: Duration) -> GoliathVehicleResult<()>;
+}
+
+/// A digital output, used for selecting the direction of a motor
+pub(crate) trait DigitalOutput: Send {
+    fn set_high(&mut self) -> GoliathVehicleResult<()>;
+    fn set_low(&mut self) -> GoliathVehicleResult<()>;
+}
+
+/// Provides the outputs the motors are wired to, pins are given by their physical number
+pub(crate) trait MotorsBackend: Send + Sync {
+    fn get_pwm(&self, pin: u8) -> GoliathVehicleResult<Box<dyn PwmChannel>>;
+    fn get_output(&self, pin: u8) -> GoliathVehicleResult<Box<dyn DigitalOutput>>;
+}
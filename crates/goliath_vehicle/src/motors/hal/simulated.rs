@@ -0,0 +1,163 @@
+use crate::error::GoliathVehicleResult;
+use crate::motors::hal::{DigitalOutput, MotorsBackend, PwmChannel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) enum OutputChange {
+    DutyCycle(u32),
+    Frequency(u32),
+    PulseWidth(Duration),
+    Level(bool),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct OutputEvent {
+    pub(crate) elapsed: Duration, // Since the creation of the backend
+    pub(crate) pin: u8,
+    pub(crate) change: OutputChange,
+}
+
+#[derive(Clone)]
+struct EventRecorder {
+    start: Instant,
+    events: Arc<Mutex<Vec<OutputEvent>>>,
+}
+
+impl EventRecorder {
+    fn record(&self, pin: u8, change: OutputChange) {
+        let event = OutputEvent {
+            elapsed: self.start.elapsed(),
+            pin,
+            change,
+        };
+        log::debug!("Simulated output changed: {event:?}");
+
+        // A poisoned lock only means another recording thread panicked, the events are still valid
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(event);
+    }
+}
+
+/// Records every change made to the motor outputs instead of driving any hardware
+pub(crate) struct SimulatedBackend {
+    recorder: EventRecorder,
+}
+
+impl SimulatedBackend {
+    pub(crate) fn new() -> Self {
+        Self {
+            recorder: EventRecorder {
+                start: Instant::now(),
+                events: Arc::new(Mutex::new(Vec::new())),
+            },
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn events(&self) -> Vec<OutputEvent> {
+        self.recorder
+            .events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Every change made to a single pin, oldest first
+    #[cfg(test)]
+    pub(crate) fn changes(&self, pin: u8) -> Vec<OutputChange> {
+        self.events()
+            .into_iter()
+            .filter(|event| event.pin == pin)
+            .map(|event| event.change)
+            .collect()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn last_duty_cycle(&self, pin: u8) -> Option<u32> {
+        self.changes(pin)
+            .into_iter()
+            .rev()
+            .find_map(|change| match change {
+                OutputChange::DutyCycle(duty_cycle) => Some(duty_cycle),
+                _ => None,
+            })
+    }
+
+    #[cfg(test)]
+    pub(crate) fn pulse_widths(&self, pin: u8) -> Vec<Duration> {
+        self.changes(pin)
+            .into_iter()
+            .filter_map(|change| match change {
+                OutputChange::PulseWidth(pulse_width) => Some(pulse_width),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn last_level(&self, pin: u8) -> Option<bool> {
+        self.changes(pin)
+            .into_iter()
+            .rev()
+            .find_map(|change| match change {
+                OutputChange::Level(high) => Some(high),
+                _ => None,
+            })
+    }
+}
+
+impl MotorsBackend for SimulatedBackend {
+    fn get_pwm(&self, pin: u8) -> GoliathVehicleResult<Box<dyn PwmChannel>> {
+        Ok(Box::new(SimulatedOutput {
+            pin,
+            recorder: self.recorder.clone(),
+        }))
+    }
+
+    fn get_output(&self, pin: u8) -> GoliathVehicleResult<Box<dyn DigitalOutput>> {
+        Ok(Box::new(SimulatedOutput {
+            pin,
+            recorder: self.recorder.clone(),
+        }))
+    }
+}
+
+struct SimulatedOutput {
+    pin: u8,
+    recorder: EventRecorder,
+}
+
+impl PwmChannel for SimulatedOutput {
+    fn set_duty_cycle(&mut self, duty_cycle: u32) -> GoliathVehicleResult<()> {
+        self.recorder
+            .record(self.pin, OutputChange::DutyCycle(duty_cycle));
+        Ok(())
+    }
+
+    fn set_frequency(&mut self, frequency: u32) -> GoliathVehicleResult<()> {
+        self.recorder
+            .record(self.pin, OutputChange::Frequency(frequency));
+        Ok(())
+    }
+
+    fn set_pulse_width(&mut self, pulse_width: Duration) -> GoliathVehicleResult<()> {
+        self.recorder
+            .record(self.pin, OutputChange::PulseWidth(pulse_width));
+        Ok(())
+    }
+}
+
+impl DigitalOutput for SimulatedOutput {
+    fn set_high(&mut self) -> GoliathVehicleResult<()> {
+        self.recorder.record(self.pin, OutputChange::Level(true));
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> GoliathVehicleResult<()> {
+        self.recorder.record(self.pin, OutputChange::Level(false));
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use goliath_common::DriveMode;
+
+/// The latest drive values received, each mixer only uses the ones relevant to its mode
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct DriveInput {
+    pub(crate) thrust: f32,
+    pub(crate) steer: f32,
+    pub(crate) left: f32,
+    pub(crate) right: f32,
+}
+
+/// Turns the drive input into the signed power of the left and right tracks
+pub(crate) trait DriveMixer: Send {
+    fn mix(&self, input: &DriveInput) -> (f32, f32);
+}
+
+pub(crate) fn mixer_for_mode(mode: DriveMode) -> Box<dyn DriveMixer> {
+    match mode {
+        DriveMode::Arcade => Box::new(ArcadeMixer),
+        DriveMode::Tank => Box::new(TankMixer),
+        DriveMode::Pivot => Box::new(PivotMixer),
+    }
+}
+
+/// Thrust drives both tracks, steer shifts power between them without exceeding full power
+struct ArcadeMixer;
+
+impl DriveMixer for ArcadeMixer {
+    fn mix(&self, input: &DriveInput) -> (f32, f32) {
+        let max_steer = if input.thrust.abs() <= 0.0 {
+            1.0 // Avoid division by zero
+        } else {
+            (1.0 - input.thrust.abs()) / input.thrust.abs()
+        };
+
+        let constrained_steer = input.steer.clamp(-max_steer, max_steer);
+
+        (
+            input.thrust * (1.0 - constrained_steer),
+            input.thrust * (1.0 + constrained_steer),
+        )
+    }
+}
+
+/// Each track is commanded directly
+struct TankMixer;
+
+impl DriveMixer for TankMixer {
+    fn mix(&self, input: &DriveInput) -> (f32, f32) {
+        (input.left, input.right)
+    }
+}
+
+/// Tracks counter-rotate to turn in place, thrust is ignored
+struct PivotMixer;
+
+impl DriveMixer for PivotMixer {
+    fn mix(&self, input: &DriveInput) -> (f32, f32) {
+        (-input.steer, input.steer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODES: [DriveMode; 3] = [DriveMode::Arcade, DriveMode::Tank, DriveMode::Pivot];
+
+    fn mix(mode: DriveMode, thrust: f32, steer: f32, left: f32, right: f32) -> (f32, f32) {
+        let input = DriveInput {
+            thrust,
+            steer,
+            left,
+            right,
+        };
+        mixer_for_mode(mode).mix(&input)
+    }
+
+    fn arcade(thrust: f32, steer: f32) -> (f32, f32) {
+        mix(DriveMode::Arcade, thrust, steer, 0.0, 0.0)
+    }
+
+    #[test]
+    fn no_input_stops_every_mode() {
+        for mode in MODES {
+            assert_eq!(mix(mode, 0.0, 0.0, 0.0, 0.0), (0.0, 0.0), "{mode:?}");
+        }
+    }
+
+    #[test]
+    fn outputs_stay_within_full_power() {
+        let steps = [-1.0, -0.75, -0.5, -0.01, 0.0, 0.01, 0.5, 0.75, 1.0];
+        for mode in MODES {
+            for first in steps {
+                for second in steps {
+                    let (left, right) = mix(mode, first, second, first, second);
+                    assert!(
+                        (-1.0..=1.0).contains(&left) && (-1.0..=1.0).contains(&right),
+                        "{mode:?} gave ({left}, {right}) for ({first}, {second})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn arcade_full_thrust_leaves_no_room_to_steer() {
+        assert_eq!(arcade(1.0, 1.0), (1.0, 1.0));
+        assert_eq!(arcade(1.0, -1.0), (1.0, 1.0));
+        assert_eq!(arcade(-1.0, 1.0), (-1.0, -1.0));
+        assert_eq!(arcade(-1.0, -1.0), (-1.0, -1.0));
+    }
+
+    #[test]
+    fn arcade_steer_shifts_power_between_tracks() {
+        assert_eq!(arcade(0.5, 1.0), (0.0, 1.0));
+        assert_eq!(arcade(0.5, -1.0), (1.0, 0.0));
+        assert_eq!(arcade(0.5, 0.5), (0.25, 0.75));
+        // Reversing keeps the sign of the thrust on both tracks
+        assert_eq!(arcade(-0.5, 1.0), (0.0, -1.0));
+        assert_eq!(arcade(-0.5, 0.5), (-0.25, -0.75));
+    }
+
+    #[test]
+    fn arcade_does_not_turn_without_thrust() {
+        assert_eq!(arcade(0.0, 1.0), (0.0, 0.0));
+        assert_eq!(arcade(0.0, -1.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn arcade_passes_inputs_inside_the_deadband() {
+        // The calibration applies the deadband, the mixer keeps small inputs and their sign
+        assert_eq!(arcade(0.01, 0.0), (0.01, 0.01));
+        assert_eq!(arcade(-0.01, 0.0), (-0.01, -0.01));
+        let (left, right) = arcade(0.01, 0.5);
+        assert!(0.0 < left && left < right && right < 0.02);
+    }
+
+    #[test]
+    fn tank_passes_tracks_through() {
+        assert_eq!(mix(DriveMode::Tank, 0.0, 0.0, 1.0, -1.0), (1.0, -1.0));
+        assert_eq!(mix(DriveMode::Tank, 0.0, 0.0, -0.3, -0.7), (-0.3, -0.7));
+        assert_eq!(mix(DriveMode::Tank, 0.0, 0.0, -0.01, 0.01), (-0.01, 0.01));
+        // Thrust and steer belong to the other modes
+        assert_eq!(mix(DriveMode::Tank, 1.0, 1.0, 0.0, 0.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn pivot_counter_rotates_the_tracks() {
+        assert_eq!(mix(DriveMode::Pivot, 0.0, 1.0, 0.0, 0.0), (-1.0, 1.0));
+        assert_eq!(mix(DriveMode::Pivot, 0.0, -0.5, 0.0, 0.0), (0.5, -0.5));
+        assert_eq!(mix(DriveMode::Pivot, 0.0, 0.01, 0.0, 0.0), (-0.01, 0.01));
+        // Thrust is ignored
+        assert_eq!(mix(DriveMode::Pivot, 1.0, 1.0, 0.0, 0.0), (-1.0, 1.0));
+        assert_eq!(mix(DriveMode::Pivot, 1.0, 0.0, 0.0, 0.0), (0.0, 0.0));
+    }
+}
@@ -1,83 +1,368 @@
 use crate::GoliathVehicleResult;
+use crate::config::MotorsConfig;
+use crate::emergency_stop::EmergencyStop;
 use crate::error::GoliathVehicleError;
+use crate::motors::hal::MotorsBackend;
 use crate::motors::tracks_driver::TracksDriver;
 use crate::motors::turret_driver::TurretDriver;
-use goliath_common::MotorCommand;
-use jetgpio::Gpio;
+use goliath_common::{MotorCommand, MotorReport};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
 use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{broadcast, mpsc, watch};
 
+mod calibration;
 mod directional_motor_pin;
+pub(crate) mod hal;
+mod mixer;
+mod power_ramp;
 mod tracks_driver;
 mod turret_driver;
 
+pub(crate) use calibration::validate_calibration;
+
+/// Latest state of the actuators, published by the motors thread every tick
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct MotorsState {
+    pub(crate) left_power: f32, // Signed, negative is backwards
+    pub(crate) right_power: f32,
+    pub(crate) turret_angle: f32,
+}
+
+/// The sessions' way to the motors thread, which outlives them
+#[derive(Clone)]
+pub(crate) struct MotorsHandle {
+    pub(crate) cmd_tx: mpsc::Sender<MotorCommand>,
+    pub(crate) report_tx: broadcast::Sender<MotorReport>, // Only for subscribing
+    pub(crate) state_rx: watch::Receiver<MotorsState>,
+}
+
+impl MotorsHandle {
+    pub(crate) fn is_running(&self) -> bool {
+        !self.cmd_tx.is_closed()
+    }
+}
+
 pub(crate) struct MotorsContoller {
     tracks_driver: TracksDriver,
-    _turret_driver: TurretDriver,
+    turret_driver: TurretDriver,
+    emergency_stop: Arc<EmergencyStop>,
+    watchdog_timeout: Duration,
+    tick: Duration,
 }
 
 impl MotorsContoller {
-    pub(crate) fn try_new(gpio: Arc<Gpio>) -> GoliathVehicleResult<Self> {
+    pub(crate) fn try_new(
+        backend: Arc<dyn MotorsBackend>,
+        emergency_stop: Arc<EmergencyStop>,
+        config: &MotorsConfig,
+    ) -> GoliathVehicleResult<Self> {
         Ok(Self {
-            tracks_driver: TracksDriver::try_new(&gpio)?,
-            _turret_driver: TurretDriver::new(&gpio)?,
+            tracks_driver: TracksDriver::try_new(backend.as_ref(), config)?,
+            turret_driver: TurretDriver::try_new(backend.as_ref(), &config.turret)?,
+            emergency_stop,
+            watchdog_timeout: Duration::from_millis(config.watchdog_timeout_ms),
+            tick: Duration::from_millis(config.ramping.tick_ms),
         })
     }
 
-    pub(crate) fn run_thread(
+    /// Runs the controller on its own thread until the vehicle stops
+    pub(crate) fn spawn(
+        mut self,
+    ) -> GoliathVehicleResult<(MotorsHandle, thread::JoinHandle<GoliathVehicleResult<()>>)> {
+        let (cmd_tx, cmd_rx) = mpsc::channel::<MotorCommand>(32);
+        let (report_tx, _) = broadcast::channel::<MotorReport>(8);
+        let (state_tx, state_rx) = watch::channel(MotorsState::default());
+        let motors_thread = thread::Builder::new()
+            .name("MotorsThread".to_string())
+            .spawn({
+                let report_tx = report_tx.clone();
+                move || self.run_thread(cmd_rx, report_tx, state_tx)
+            })?;
+
+        let handle = MotorsHandle {
+            cmd_tx,
+            report_tx,
+            state_rx,
+        };
+        Ok((handle, motors_thread))
+    }
+
+    fn is_stationary(&self) -> bool {
+        self.tracks_driver.is_stationary() && self.turret_driver.is_stationary()
+    }
+
+    fn run_thread(
         &mut self,
         mut cmd_channel: mpsc::Receiver<MotorCommand>,
+        report_channel: broadcast::Sender<MotorReport>,
+        state_channel: watch::Sender<MotorsState>,
     ) -> GoliathVehicleResult<()> {
-        let mut modified_tracks = false;
-        let mut msg_count = 0;
+        let mut next_tick = Instant::now();
+
+        // Only armed once a command arrives, so an idle vehicle does not keep triggering it
+        let mut last_command = Instant::now();
+        let mut watchdog_armed = false;
+        let mut emergency_stopped = false;
         loop {
-            if msg_count > 5 {
-                if modified_tracks {
-                    self.tracks_driver.update_tracks()?;
-                    modified_tracks = false;
-                }
-                msg_count = 0;
-            }
-            match cmd_channel.try_recv() {
-                Ok(cmd) => {
-                    msg_count += 1;
-                    match cmd {
-                        MotorCommand::Thrust(thrust) => {
-                            self.tracks_driver.set_thrust(thrust);
-                            modified_tracks = true;
-                        }
-                        MotorCommand::Steer(steer) => {
-                            self.tracks_driver.set_steer(steer);
-                            modified_tracks = true;
+            // Everything that arrived since the last tick is applied at once
+            let mut modified_tracks = false;
+            loop {
+                match cmd_channel.try_recv() {
+                    // The session already rejects these, but nothing may move while latched
+                    Ok(cmd) if cmd.is_motion() && self.emergency_stop.is_engaged() => {
+                        log::warn!("Ignoring {cmd:?} while the emergency stop is engaged");
+                    }
+                    Ok(cmd) => {
+                        // Heartbeats keep the link alive while stationary, but can't keep a
+                        // vehicle moving on its own if the commands stopped coming
+                        if !matches!(cmd, MotorCommand::Heartbeat) || self.is_stationary() {
+                            last_command = Instant::now();
+                            watchdog_armed = true;
                         }
-                        MotorCommand::TurretAngle(_) => {}
-                        MotorCommand::End => {
-                            log::info!("Got END command, stopping motors");
-                            self.tracks_driver.set_thrust(0.0);
-                            self.tracks_driver.set_steer(0.0);
-                            self.tracks_driver.update_tracks()?;
-                            break;
+
+                        match cmd {
+                            MotorCommand::Thrust(thrust) => {
+                                self.tracks_driver.set_thrust(thrust);
+                                modified_tracks = true;
+                            }
+                            MotorCommand::Steer(steer) => {
+                                self.tracks_driver.set_steer(steer);
+                                modified_tracks = true;
+                            }
+                            MotorCommand::LeftTrack(power) => {
+                                self.tracks_driver.set_left(power);
+                                modified_tracks = true;
+                            }
+                            MotorCommand::RightTrack(power) => {
+                                self.tracks_driver.set_right(power);
+                                modified_tracks = true;
+                            }
+                            MotorCommand::Drive { thrust, steer } => {
+                                self.tracks_driver.set_thrust(thrust);
+                                self.tracks_driver.set_steer(steer);
+                                modified_tracks = true;
+                            }
+                            MotorCommand::Tank { left, right } => {
+                                self.tracks_driver.set_left(left);
+                                self.tracks_driver.set_right(right);
+                                modified_tracks = true;
+                            }
+                            MotorCommand::SetDriveMode(mode) => {
+                                self.tracks_driver.set_mode(mode);
+                                modified_tracks = true;
+                            }
+                            MotorCommand::Calibrate { track, calibration } => {
+                                self.tracks_driver.set_calibration(track, calibration)?;
+                            }
+                            MotorCommand::TurretAngle(angle) => self.turret_driver.set_angle(angle),
+                            MotorCommand::TurretRate(rate) => self.turret_driver.set_rate(rate),
+                            MotorCommand::TurretHome => self.turret_driver.home(),
+                            MotorCommand::Heartbeat => {}
+                            MotorCommand::End => {
+                                log::info!("Got END command, stopping motors");
+                                self.tracks_driver.stop()?;
+                                return Ok(());
+                            }
                         }
                     }
-                }
-                Err(TryRecvError::Empty) => {
-                    if modified_tracks {
-                        self.tracks_driver.update_tracks()?;
-                        modified_tracks = false;
+                    Err(TryRecvError::Empty) => break,
+                    Err(e @ TryRecvError::Disconnected) => {
+                        log::info!("Motor command channel disconnected, stopping motors.");
+                        self.tracks_driver.stop()?;
+                        return Err(GoliathVehicleError::from(e));
                     }
-                    msg_count = 0;
                 }
-                Err(e @ TryRecvError::Disconnected) => {
-                    log::info!("Motor command channel disconnected, stopping motors.");
-                    self.tracks_driver.set_thrust(0.0);
-                    self.tracks_driver.set_steer(0.0);
-                    self.tracks_driver.update_tracks()?;
-                    return Err(GoliathVehicleError::from(e));
+            }
+
+            if watchdog_armed && last_command.elapsed() >= self.watchdog_timeout {
+                log::warn!(
+                    "No motor command for {:?}, watchdog is stopping the tracks",
+                    self.watchdog_timeout
+                );
+                self.tracks_driver.release();
+                self.turret_driver.set_rate(0.0);
+                modified_tracks = true;
+                watchdog_armed = false;
+
+                // Never blocks, and fails only if no operator is connected to be told
+                if report_channel
+                    .send(MotorReport::WatchdogTriggered {
+                        timeout_ms: self.watchdog_timeout.as_millis() as u64,
+                    })
+                    .is_err()
+                {
+                    log::debug!("No session to report the watchdog trigger to");
                 }
             }
+
+            match (self.emergency_stop.is_engaged(), emergency_stopped) {
+                (true, false) => {
+                    log::warn!("Emergency stop engaged, stopping all motors");
+                    self.tracks_driver.stop()?;
+                    self.turret_driver.halt();
+                    emergency_stopped = true;
+                    modified_tracks = false;
+                }
+                (false, true) => {
+                    log::info!("Emergency stop cleared, accepting motion commands");
+                    emergency_stopped = false;
+                }
+                _ => {}
+            }
+
+            if modified_tracks {
+                self.tracks_driver.update_tracks();
+            }
+            self.tracks_driver.apply_ramps(self.tick)?;
+            self.turret_driver.tick(self.tick)?;
+
+            let (left_power, right_power) = self.tracks_driver.powers();
+            let state = MotorsState {
+                left_power,
+                right_power,
+                turret_angle: self.turret_driver.angle(),
+            };
+            // The sessions only sample it for telemetry, so nobody needs to be notified
+            state_channel.send_replace(state);
+
+            // Don't try to catch up on missed ticks, that would only make the ramps jerky
+            next_tick += self.tick;
+            match next_tick.checked_duration_since(Instant::now()) {
+                Some(remaining) => thread::sleep(remaining),
+                None => next_tick = Instant::now(),
+            }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motors::hal::SimulatedBackend;
+
+    // Many ticks, so the thread has settled whatever the scheduling
+    const SETTLE: Duration = Duration::from_millis(200);
+
+    fn spawn(
+        config: &MotorsConfig,
+        emergency_stop: Arc<EmergencyStop>,
+    ) -> (
+        Arc<SimulatedBackend>,
+        MotorsHandle,
+        thread::JoinHandle<GoliathVehicleResult<()>>,
+    ) {
+        let backend = Arc::new(SimulatedBackend::new());
+        let (handle, motors_thread) =
+            MotorsContoller::try_new(Arc::clone(&backend) as _, emergency_stop, config)
+                .unwrap()
+                .spawn()
+                .unwrap();
+        (backend, handle, motors_thread)
+    }
+
+    fn config() -> MotorsConfig {
+        let mut config = MotorsConfig::default();
+        config.ramping.tick_ms = 5;
+        config.ramping.max_acceleration = 100.0;
+        config.ramping.max_deceleration = 100.0;
+        config.watchdog_timeout_ms = 10_000;
+        config
+    }
+
+    #[test]
+    fn drives_until_the_end_command() {
+        let config = config();
+        let (backend, handle, motors_thread) = spawn(&config, Arc::new(EmergencyStop::new()));
+
+        handle
+            .cmd_tx
+            .blocking_send(MotorCommand::Drive {
+                thrust: 1.0,
+                steer: 0.0,
+            })
+            .unwrap();
+        thread::sleep(SETTLE);
+        assert_eq!(
+            backend.last_duty_cycle(config.left_track.pwm_pin),
+            Some(100)
+        );
+        assert_eq!(
+            backend.last_duty_cycle(config.right_track.pwm_pin),
+            Some(100)
+        );
+        assert_eq!(
+            backend.last_level(config.left_track.forward_pin),
+            Some(true)
+        );
+        assert_eq!(handle.state_rx.borrow().left_power, 1.0);
+
+        handle.cmd_tx.blocking_send(MotorCommand::End).unwrap();
+        motors_thread.join().unwrap().unwrap();
+        assert_eq!(backend.last_duty_cycle(config.left_track.pwm_pin), Some(0));
+        assert_eq!(backend.last_duty_cycle(config.right_track.pwm_pin), Some(0));
+    }
+
+    #[test]
+    fn watchdog_releases_the_tracks() {
+        let mut config = config();
+        config.watchdog_timeout_ms = 50;
+        let (backend, handle, motors_thread) = spawn(&config, Arc::new(EmergencyStop::new()));
+        let mut report_rx = handle.report_tx.subscribe();
+
+        handle
+            .cmd_tx
+            .blocking_send(MotorCommand::Drive {
+                thrust: 1.0,
+                steer: 0.0,
+            })
+            .unwrap();
+        assert_eq!(
+            report_rx.blocking_recv().unwrap(),
+            MotorReport::WatchdogTriggered { timeout_ms: 50 }
+        );
+        thread::sleep(SETTLE);
+        assert_eq!(backend.last_duty_cycle(config.left_track.pwm_pin), Some(0));
+        assert_eq!(backend.last_duty_cycle(config.right_track.pwm_pin), Some(0));
+
+        handle.cmd_tx.blocking_send(MotorCommand::End).unwrap();
+        motors_thread.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn emergency_stop_ignores_motion() {
+        let config = config();
+        let emergency_stop = Arc::new(EmergencyStop::new());
+        emergency_stop.engage();
+        let (backend, handle, motors_thread) = spawn(&config, Arc::clone(&emergency_stop));
+
+        handle
+            .cmd_tx
+            .blocking_send(MotorCommand::Drive {
+                thrust: 1.0,
+                steer: 0.0,
+            })
+            .unwrap();
+        thread::sleep(SETTLE);
+        assert_eq!(backend.last_duty_cycle(config.left_track.pwm_pin), Some(0));
+
+        // Cleared, motion is accepted again
+        emergency_stop.clear();
+        handle
+            .cmd_tx
+            .blocking_send(MotorCommand::Drive {
+                thrust: 1.0,
+                steer: 0.0,
+            })
+            .unwrap();
+        thread::sleep(SETTLE);
+        assert_eq!(
+            backend.last_duty_cycle(config.left_track.pwm_pin),
+            Some(100)
+        );
 
-        Ok(())
+        handle.cmd_tx.blocking_send(MotorCommand::End).unwrap();
+        motors_thread.join().unwrap().unwrap();
     }
 }
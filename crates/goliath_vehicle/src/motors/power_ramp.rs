@@ -0,0 +1,73 @@
+use crate::config::RampingConfig;
+use std::time::Duration;
+
+/// Limits how fast the power of a single track may change, power is signed, negative is backwards
+pub(crate) struct PowerRamp {
+    max_acceleration: f32,
+    max_deceleration: f32,
+    reversal_dwell: Duration,
+    current: f32,
+    target: f32,
+    dwell_remaining: Duration,
+}
+
+impl PowerRamp {
+    pub(crate) fn new(config: &RampingConfig) -> Self {
+        Self {
+            max_acceleration: config.max_acceleration,
+            max_deceleration: config.max_deceleration,
+            reversal_dwell: Duration::from_millis(config.reversal_dwell_ms),
+            current: 0.0,
+            target: 0.0,
+            dwell_remaining: Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn set_target(&mut self, target: f32) {
+        self.target = target;
+    }
+
+    pub(crate) fn current(&self) -> f32 {
+        self.current
+    }
+
+    /// Bypasses the ramp, only for when the motors must stop right away
+    pub(crate) fn reset(&mut self) {
+        self.current = 0.0;
+        self.target = 0.0;
+        self.dwell_remaining = Duration::ZERO;
+    }
+
+    /// Advances the ramp by a single tick, returning the new power
+    pub(crate) fn step(&mut self, elapsed: Duration) -> f32 {
+        if !self.dwell_remaining.is_zero() {
+            self.dwell_remaining = self.dwell_remaining.saturating_sub(elapsed);
+            return self.current;
+        }
+
+        // A reversal has to come to a full stop first, and wait there for the dwell time
+        let reversing = self.current != 0.0
+            && self.target != 0.0
+            && self.current.is_sign_positive() != self.target.is_sign_positive();
+        let goal = if reversing { 0.0 } else { self.target };
+
+        let rate = if goal.abs() > self.current.abs() {
+            self.max_acceleration
+        } else {
+            self.max_deceleration
+        };
+        let max_step = rate * elapsed.as_secs_f32();
+        let delta = goal - self.current;
+        if delta.abs() <= max_step {
+            self.current = goal;
+        } else {
+            self.current += max_step.copysign(delta);
+        }
+
+        if reversing && self.current == 0.0 {
+            self.dwell_remaining = self.reversal_dwell;
+        }
+
+        self.current
+    }
+}
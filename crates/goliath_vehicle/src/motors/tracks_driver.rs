@@ -1,83 +1,174 @@
 use crate::GoliathVehicleResult;
+use crate::config::MotorsConfig;
+use crate::motors::calibration::calibrated_power;
 use crate::motors::directional_motor_pin::DirectionalMotorPin;
-use jetgpio::Gpio;
-use jetgpio::gpio::valid_pins;
-use jetgpio::pwm::Pwm;
-use std::sync::Arc;
+use crate::motors::hal::MotorsBackend;
+use crate::motors::mixer::{DriveInput, DriveMixer, mixer_for_mode};
+use crate::motors::power_ramp::PowerRamp;
+use goliath_common::{DriveMode, Track, TrackCalibration};
+use std::time::Duration;
 
 pub(crate) struct TracksDriver {
     left_track: DirectionalMotorPin,
     right_track: DirectionalMotorPin,
-    thrust: f32,
-    steer: f32,
+    left_ramp: PowerRamp,
+    right_ramp: PowerRamp,
+    left_calibration: TrackCalibration,
+    right_calibration: TrackCalibration,
+    mode: DriveMode,
+    mixer: Box<dyn DriveMixer>,
+    input: DriveInput,
 }
 
 impl TracksDriver {
-    pub(crate) fn try_new(gpio: &Arc<Gpio>) -> GoliathVehicleResult<Self> {
-        let mut left_track = DirectionalMotorPin::try_new(
-            Pwm::new(valid_pins::Pin32)?,
-            gpio.get_output(valid_pins::Pin3)?,
-            gpio.get_output(valid_pins::Pin4)?,
-        )?;
+    pub(crate) fn try_new(
+        backend: &dyn MotorsBackend,
+        config: &MotorsConfig,
+    ) -> GoliathVehicleResult<Self> {
+        let mut left_track = DirectionalMotorPin::try_new(backend, config.left_track)?;
         left_track.set_power_and_direction(0.0, true)?; // Ensure the motor is stopped on init
 
-        let mut right_track = DirectionalMotorPin::try_new(
-            Pwm::new(valid_pins::Pin32)?,
-            gpio.get_output(valid_pins::Pin3)?,
-            gpio.get_output(valid_pins::Pin4)?,
-        )?;
+        let mut right_track = DirectionalMotorPin::try_new(backend, config.right_track)?;
         right_track.set_power_and_direction(0.0, true)?; // Ensure the motor is stopped on init
 
         Ok(Self {
             left_track,
             right_track,
-            thrust: 0.0,
-            steer: 0.0,
+            left_ramp: PowerRamp::new(&config.ramping),
+            right_ramp: PowerRamp::new(&config.ramping),
+            left_calibration: config.calibration.left.clone(),
+            right_calibration: config.calibration.right.clone(),
+            mode: config.drive_mode,
+            mixer: mixer_for_mode(config.drive_mode),
+            input: DriveInput::default(),
         })
     }
 
+    // Commands are validated by the session, clamping here only guards the motors themselves
     pub(crate) fn set_thrust(&mut self, thrust: f32) {
         // Thrust has to be between -1.0 and 1.0
-        assert!((-1.0..=1.0).contains(&thrust));
-        self.thrust = thrust;
+        self.input.thrust = thrust.clamp(-1.0, 1.0);
     }
 
     pub(crate) fn set_steer(&mut self, steer: f32) {
         // Ratio between the left and right tracks has to be between -1.0 and 1.0
-        assert!((-1.0..=1.0).contains(&steer));
-        self.steer = steer;
-    }
-
-    pub(crate) fn update_tracks(&mut self) -> GoliathVehicleResult<()> {
-        let max_steer = if self.thrust.abs() <= 0.0 {
-            1.0 // Avoid division by zero
-        } else {
-            (1.0 - self.thrust.abs()) / self.thrust.abs()
-        };
-
-        let constrained_steer = self.steer.clamp(-max_steer, max_steer);
-
-        let left_power = self.thrust * (1.0 - constrained_steer);
-        let right_power = self.thrust * (1.0 + constrained_steer);
-
-        let left_forward = left_power >= 0.0;
-        let right_forward = right_power >= 0.0;
-
-        self.left_track
-            .set_power_and_direction(left_power.abs() as f64, left_forward)?;
-        log::info!(
-            "Left track power set to {}, moving forward: {left_forward}",
-            left_power.abs()
-        );
-        self.right_track
-            .set_power_and_direction(right_power.abs() as f64, right_forward)?;
-        log::info!(
-            "Right track power set to {}, moving forward: {right_forward}",
-            right_power.abs()
-        );
+        self.input.steer = steer.clamp(-1.0, 1.0);
+    }
+
+    pub(crate) fn set_left(&mut self, power: f32) {
+        self.input.left = power.clamp(-1.0, 1.0);
+    }
+
+    pub(crate) fn set_right(&mut self, power: f32) {
+        self.input.right = power.clamp(-1.0, 1.0);
+    }
+
+    /// Inputs are dropped on a mode change, so values meant for the previous mode can't
+    /// suddenly start driving the tracks
+    pub(crate) fn set_mode(&mut self, mode: DriveMode) {
+        if mode == self.mode {
+            return;
+        }
+
+        self.mode = mode;
+        self.mixer = mixer_for_mode(mode);
+        self.input = DriveInput::default();
+        log::info!("Drive mode set to {mode:?}");
+    }
+
+    /// Replaces the calibration of a track, and applies it to the track's current power. Only
+    /// kept until the vehicle restarts, the config's calibration is used again from then on
+    pub(crate) fn set_calibration(
+        &mut self,
+        track: Track,
+        calibration: TrackCalibration,
+    ) -> GoliathVehicleResult<()> {
+        // Logged in full so tuned values can be copied into the vehicle config
+        log::info!("{track:?} track calibration set to {calibration:?} until restarting");
+        match track {
+            Track::Left => {
+                self.left_calibration = calibration;
+                Self::apply_power(
+                    &mut self.left_track,
+                    &self.left_calibration,
+                    self.left_ramp.current(),
+                )
+            }
+            Track::Right => {
+                self.right_calibration = calibration;
+                Self::apply_power(
+                    &mut self.right_track,
+                    &self.right_calibration,
+                    self.right_ramp.current(),
+                )
+            }
+        }
+    }
+
+    /// Zeroes every input, leaving the ramps to bring the tracks to a stop
+    pub(crate) fn release(&mut self) {
+        self.input = DriveInput::default();
+    }
+
+    pub(crate) fn is_stationary(&self) -> bool {
+        let (left_power, right_power) = self.mixer.mix(&self.input);
+        left_power == 0.0 && right_power == 0.0
+    }
+
+    /// Signed power currently applied to the left and right tracks, before calibration
+    pub(crate) fn powers(&self) -> (f32, f32) {
+        (self.left_ramp.current(), self.right_ramp.current())
+    }
+
+    /// Computes the requested power of each track, which the ramps will then follow
+    pub(crate) fn update_tracks(&mut self) {
+        let (left_power, right_power) = self.mixer.mix(&self.input);
+
+        self.left_ramp.set_target(left_power);
+        self.right_ramp.set_target(right_power);
+        log::info!("Tracks target power set to left: {left_power}, right: {right_power}");
+    }
+
+    /// Advances the ramps by a single tick, only writing to the motors if the power changed
+    pub(crate) fn apply_ramps(&mut self, elapsed: Duration) -> GoliathVehicleResult<()> {
+        let previous_left = self.left_ramp.current();
+        let left_power = self.left_ramp.step(elapsed);
+        if left_power != previous_left {
+            Self::apply_power(&mut self.left_track, &self.left_calibration, left_power)?;
+            log::debug!("Left track power set to {left_power}");
+        }
+
+        let previous_right = self.right_ramp.current();
+        let right_power = self.right_ramp.step(elapsed);
+        if right_power != previous_right {
+            Self::apply_power(&mut self.right_track, &self.right_calibration, right_power)?;
+            log::debug!("Right track power set to {right_power}");
+        }
 
         Ok(())
     }
+
+    /// Stops both tracks immediately, without ramping down
+    pub(crate) fn stop(&mut self) -> GoliathVehicleResult<()> {
+        self.input = DriveInput::default();
+        self.left_ramp.reset();
+        self.right_ramp.reset();
+
+        self.left_track.set_power_and_direction(0.0, true)?;
+        self.right_track.set_power_and_direction(0.0, true)?;
+        log::info!("Tracks stopped");
+
+        Ok(())
+    }
+
+    fn apply_power(
+        track: &mut DirectionalMotorPin,
+        calibration: &TrackCalibration,
+        power: f32,
+    ) -> GoliathVehicleResult<()> {
+        let calibrated = calibrated_power(calibration, power);
+        track.set_power_and_direction(calibrated.abs() as f64, calibrated >= 0.0)
+    }
 }
 
 impl Drop for TracksDriver {
@@ -90,3 +181,108 @@ impl Drop for TracksDriver {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TrackWiring;
+    use crate::motors::hal::SimulatedBackend;
+
+    // Longer than any ramp or dwell takes with the default config
+    const SETTLE: Duration = Duration::from_secs(1);
+
+    fn driver(config: &MotorsConfig) -> (SimulatedBackend, TracksDriver) {
+        let backend = SimulatedBackend::new();
+        let driver = TracksDriver::try_new(&backend, config).unwrap();
+        (backend, driver)
+    }
+
+    fn assert_track(
+        backend: &SimulatedBackend,
+        wiring: TrackWiring,
+        forward: bool,
+        duty_cycle: u32,
+    ) {
+        assert_eq!(backend.last_duty_cycle(wiring.pwm_pin), Some(duty_cycle));
+        assert_eq!(backend.last_level(wiring.forward_pin), Some(forward));
+        assert_eq!(backend.last_level(wiring.backward_pin), Some(!forward));
+    }
+
+    #[test]
+    fn starts_stopped() {
+        let config = MotorsConfig::default();
+        let (backend, _driver) = driver(&config);
+
+        assert_track(&backend, config.left_track, true, 0);
+        assert_track(&backend, config.right_track, true, 0);
+    }
+
+    #[test]
+    fn power_follows_the_ramp() {
+        let config = MotorsConfig::default();
+        let (backend, mut driver) = driver(&config);
+
+        driver.set_thrust(1.0);
+        driver.update_tracks();
+        driver
+            .apply_ramps(Duration::from_millis(config.ramping.tick_ms))
+            .unwrap();
+        let partial = backend.last_duty_cycle(config.left_track.pwm_pin).unwrap();
+        assert!(partial > 0 && partial < 100, "ramped to {partial}");
+
+        driver.apply_ramps(SETTLE).unwrap();
+        assert_track(&backend, config.left_track, true, 100);
+        assert_track(&backend, config.right_track, true, 100);
+        assert_eq!(driver.powers(), (1.0, 1.0));
+    }
+
+    #[test]
+    fn reversal_stops_and_dwells_first() {
+        let config = MotorsConfig::default();
+        let (backend, mut driver) = driver(&config);
+        driver.set_thrust(1.0);
+        driver.update_tracks();
+        driver.apply_ramps(SETTLE).unwrap();
+
+        driver.set_thrust(-1.0);
+        driver.update_tracks();
+        driver.apply_ramps(SETTLE).unwrap();
+        assert_track(&backend, config.left_track, true, 0);
+
+        // Still dwelling, nothing is written
+        let written = backend.events().len();
+        driver.apply_ramps(SETTLE).unwrap();
+        assert_eq!(backend.events().len(), written);
+
+        driver.apply_ramps(SETTLE).unwrap();
+        assert_track(&backend, config.left_track, false, 100);
+        assert_track(&backend, config.right_track, false, 100);
+    }
+
+    #[test]
+    fn inverted_track_swaps_direction_pins() {
+        let mut config = MotorsConfig::default();
+        config.left_track.inverted = true;
+        let (backend, mut driver) = driver(&config);
+
+        driver.set_thrust(1.0);
+        driver.update_tracks();
+        driver.apply_ramps(SETTLE).unwrap();
+        assert_track(&backend, config.left_track, false, 100);
+        assert_track(&backend, config.right_track, true, 100);
+    }
+
+    #[test]
+    fn stop_skips_the_ramp() {
+        let config = MotorsConfig::default();
+        let (backend, mut driver) = driver(&config);
+        driver.set_thrust(1.0);
+        driver.update_tracks();
+        driver.apply_ramps(SETTLE).unwrap();
+
+        driver.stop().unwrap();
+        assert_track(&backend, config.left_track, true, 0);
+        assert_track(&backend, config.right_track, true, 0);
+        assert!(driver.is_stationary());
+    }
+}
@@ -1,11 +1,199 @@
+use crate::config::TurretConfig;
 use crate::error::GoliathVehicleResult;
-use jetgpio::Gpio;
-use std::sync::Arc;
+use crate::motors::hal::{MotorsBackend, PwmChannel};
+use std::time::Duration;
 
-pub(crate) struct TurretDriver {}
+pub(crate) struct TurretDriver {
+    servo: Box<dyn PwmChannel>,
+    config: TurretConfig,
+    current_angle: f32,
+    target_angle: f32,
+    rate: f32, // Degrees per second, only used in rate mode
+}
 
 impl TurretDriver {
-    pub(crate) fn new(_gpio: &Arc<Gpio>) -> GoliathVehicleResult<Self> {
-        Ok(Self {})
+    pub(crate) fn try_new(
+        backend: &dyn MotorsBackend,
+        config: &TurretConfig,
+    ) -> GoliathVehicleResult<Self> {
+        let mut servo = backend.get_pwm(config.pwm_pin)?;
+        servo.set_frequency(config.frequency_hz)?;
+
+        let mut turret = Self {
+            servo,
+            config: config.clone(),
+            current_angle: config.home_angle,
+            target_angle: config.home_angle,
+            rate: 0.0,
+        };
+        // The servo's actual position is unknown on init, so it is sent home at its own speed
+        turret.write_angle(config.home_angle)?;
+
+        Ok(turret)
+    }
+
+    pub(crate) fn set_angle(&mut self, angle: f32) {
+        self.rate = 0.0;
+        self.target_angle = angle.clamp(self.config.min_angle, self.config.max_angle);
+    }
+
+    /// Rate is a fraction of the max speed, between -1.0 and 1.0
+    pub(crate) fn set_rate(&mut self, rate: f32) {
+        self.rate = rate.clamp(-1.0, 1.0) * self.config.max_speed;
+    }
+
+    pub(crate) fn home(&mut self) {
+        self.set_angle(self.config.home_angle);
+    }
+
+    /// Holds the turret where it currently is
+    pub(crate) fn halt(&mut self) {
+        self.rate = 0.0;
+        self.target_angle = self.current_angle;
+    }
+
+    pub(crate) fn angle(&self) -> f32 {
+        self.current_angle
+    }
+
+    pub(crate) fn is_stationary(&self) -> bool {
+        self.rate == 0.0 && self.current_angle == self.target_angle
+    }
+
+    /// Moves the turret towards its target by a single tick, limited by the max speed
+    pub(crate) fn tick(&mut self, elapsed: Duration) -> GoliathVehicleResult<()> {
+        let elapsed_secs = elapsed.as_secs_f32();
+        if self.rate != 0.0 {
+            self.target_angle = (self.current_angle + self.rate * elapsed_secs)
+                .clamp(self.config.min_angle, self.config.max_angle);
+        }
+
+        if self.current_angle == self.target_angle {
+            return Ok(());
+        }
+
+        let max_step = self.config.max_speed * elapsed_secs;
+        let delta = self.target_angle - self.current_angle;
+        let angle = if delta.abs() <= max_step {
+            self.target_angle
+        } else {
+            self.current_angle + max_step.copysign(delta)
+        };
+
+        self.write_angle(angle)
+    }
+
+    fn write_angle(&mut self, angle: f32) -> GoliathVehicleResult<()> {
+        let angle_fraction =
+            (angle - self.config.min_angle) / (self.config.max_angle - self.config.min_angle);
+        let pulse_range_us = (self.config.max_pulse_us - self.config.min_pulse_us) as f32;
+        let pulse_us = self.config.min_pulse_us as f32 + angle_fraction * pulse_range_us;
+
+        self.servo
+            .set_pulse_width(Duration::from_secs_f32(pulse_us / 1_000_000.0))?;
+        self.current_angle = angle;
+        log::debug!("Turret angle set to {angle}");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::motors::hal::{OutputChange, SimulatedBackend};
+
+    const TICK: Duration = Duration::from_millis(100);
+
+    fn turret() -> (SimulatedBackend, TurretDriver, TurretConfig) {
+        let backend = SimulatedBackend::new();
+        let config = TurretConfig::default();
+        let turret = TurretDriver::try_new(&backend, &config).unwrap();
+        (backend, turret, config)
+    }
+
+    fn last_pulse_us(backend: &SimulatedBackend, config: &TurretConfig) -> f64 {
+        let pulse_width = *backend.pulse_widths(config.pwm_pin).last().unwrap();
+        pulse_width.as_secs_f64() * 1_000_000.0
+    }
+
+    fn assert_pulse_us(backend: &SimulatedBackend, config: &TurretConfig, expected: f64) {
+        let pulse_us = last_pulse_us(backend, config);
+        assert!(
+            (pulse_us - expected).abs() < 1.0,
+            "pulse of {pulse_us}us, expected {expected}us"
+        );
+    }
+
+    fn settle(turret: &mut TurretDriver) {
+        for _ in 0..50 {
+            turret.tick(TICK).unwrap();
+        }
+    }
+
+    #[test]
+    fn starts_home_at_the_configured_frequency() {
+        let (backend, turret, config) = turret();
+        assert_eq!(
+            backend.changes(config.pwm_pin).first(),
+            Some(&OutputChange::Frequency(config.frequency_hz))
+        );
+        assert_pulse_us(&backend, &config, 1500.0);
+        assert_eq!(turret.angle(), config.home_angle);
+    }
+
+    #[test]
+    fn angle_range_maps_to_pulse_range() {
+        let (backend, mut turret, config) = turret();
+
+        turret.set_angle(config.max_angle);
+        settle(&mut turret);
+        assert_pulse_us(&backend, &config, f64::from(config.max_pulse_us));
+
+        turret.set_angle(config.min_angle);
+        settle(&mut turret);
+        assert_pulse_us(&backend, &config, f64::from(config.min_pulse_us));
+
+        turret.set_angle(45.0);
+        settle(&mut turret);
+        assert_pulse_us(&backend, &config, 1750.0);
+    }
+
+    #[test]
+    fn angles_are_clamped_to_the_limits() {
+        let (backend, mut turret, config) = turret();
+
+        turret.set_angle(720.0);
+        settle(&mut turret);
+        assert_eq!(turret.angle(), config.max_angle);
+        assert_pulse_us(&backend, &config, f64::from(config.max_pulse_us));
+
+        turret.set_rate(-1.0);
+        settle(&mut turret);
+        assert_eq!(turret.angle(), config.min_angle);
+        assert_pulse_us(&backend, &config, f64::from(config.min_pulse_us));
+    }
+
+    #[test]
+    fn slews_at_the_max_speed() {
+        let (backend, mut turret, config) = turret();
+        let written = backend.pulse_widths(config.pwm_pin).len();
+
+        turret.set_angle(config.max_angle);
+        turret.tick(TICK).unwrap();
+        let step = config.max_speed * TICK.as_secs_f32();
+        assert_eq!(turret.angle(), config.home_angle + step);
+        assert_eq!(backend.pulse_widths(config.pwm_pin).len(), written + 1);
+
+        // Half the speed in rate mode
+        turret.set_rate(0.5);
+        turret.tick(TICK).unwrap();
+        assert_eq!(turret.angle(), config.home_angle + step * 1.5);
+        assert!(!turret.is_stationary());
+
+        turret.halt();
+        turret.tick(TICK).unwrap();
+        assert!(turret.is_stationary());
+        assert_eq!(backend.pulse_widths(config.pwm_pin).len(), written + 2);
     }
 }
@@ -0,0 +1,434 @@
+use crate::GoliathVehicleResult;
+use crate::config::AuthConfig;
+use crate::error::GoliathVehicleError;
+use crate::server::OperatorWebSocket;
+use futures_util::SinkExt;
+use futures_util::stream::StreamExt;
+use goliath_common::{AuthMessage, AuthProof, Role};
+use ring::digest::{SHA256, digest};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{ED25519, UnparsedPublicKey};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+const AUTH_TIMEOUT: Duration = Duration::from_secs(5);
+const NONCE_LEN: usize = 32;
+const ED25519_PUBLIC_KEY_LEN: usize = 32;
+
+struct AuthorizedKey {
+    role: Role,
+    public_key: Vec<u8>,
+    comment: String,
+}
+
+/// Checks the credentials of every new connection against the vehicle's config
+pub(crate) struct OperatorAuthenticator {
+    enabled: bool,
+    // Hashed, so comparing them doesn't leak the tokens through timing
+    driver_token: Option<Vec<u8>>,
+    observer_token: Option<Vec<u8>>,
+    safety_token: Option<Vec<u8>>,
+    authorized_keys: Vec<AuthorizedKey>,
+    rng: SystemRandom,
+}
+
+impl OperatorAuthenticator {
+    pub(crate) fn try_new(config: &AuthConfig) -> GoliathVehicleResult<Self> {
+        let authorized_keys = match &config.authorized_keys_path {
+            Some(path) => Self::read_authorized_keys(&std::fs::read_to_string(path)?)?,
+            None => Vec::new(),
+        };
+
+        if !config.is_enabled() {
+            log::warn!("No authentication configured, any operator can drive the vehicle");
+        }
+
+        Ok(Self {
+            enabled: config.is_enabled(),
+            driver_token: config.driver_token.as_deref().map(Self::hash_token),
+            observer_token: config.observer_token.as_deref().map(Self::hash_token),
+            safety_token: config.safety_token.as_deref().map(Self::hash_token),
+            authorized_keys,
+            rng: SystemRandom::new(),
+        })
+    }
+
+    fn hash_token(token: &str) -> Vec<u8> {
+        digest(&SHA256, token.as_bytes()).as_ref().to_vec()
+    }
+
+    fn read_authorized_keys(contents: &str) -> GoliathVehicleResult<Vec<AuthorizedKey>> {
+        let mut keys: Vec<AuthorizedKey> = Vec::new();
+        for (line_number, line) in contents.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = |reason: &str| {
+                GoliathVehicleError::ConfigError(format!(
+                    "Authorized keys line {}: {reason}",
+                    line_number + 1
+                ))
+            };
+
+            let mut fields = line.split_whitespace();
+            let role = match fields.next() {
+                Some("driver") => Role::Driver,
+                Some("observer") => Role::Observer,
+                Some("safety") => Role::Safety,
+                _ => return Err(invalid("role must be driver, observer or safety")),
+            };
+            let public_key = fields
+                .next()
+                .and_then(parse_hex)
+                .filter(|key| key.len() == ED25519_PUBLIC_KEY_LEN)
+                .ok_or_else(|| invalid("expected a hex ed25519 public key"))?;
+            // With a key listed twice, the role it gets would depend on the order of the lines
+            if keys.iter().any(|key| key.public_key == public_key) {
+                return Err(invalid("public key is already authorized"));
+            }
+            let comment = fields.collect::<Vec<_>>().join(" ");
+
+            keys.push(AuthorizedKey {
+                role,
+                public_key,
+                comment,
+            });
+        }
+
+        Ok(keys)
+    }
+
+    /// Returns the requested role if the proof allows it, or why it doesn't
+    fn verify(&self, nonce: &[u8], requested: Role, proof: &AuthProof) -> Result<Role, String> {
+        if !self.enabled {
+            return Ok(requested);
+        }
+
+        let granted = match proof {
+            AuthProof::None => return Err("No credentials provided".to_string()),
+            AuthProof::Token(token) => {
+                let token = Some(Self::hash_token(token));
+                if token == self.safety_token {
+                    Role::Safety
+                } else if token == self.driver_token {
+                    Role::Driver
+                } else if token == self.observer_token {
+                    Role::Observer
+                } else {
+                    return Err("Unknown token".to_string());
+                }
+            }
+            AuthProof::Ed25519 {
+                public_key,
+                signature,
+            } => {
+                let key = self
+                    .authorized_keys
+                    .iter()
+                    .find(|key| key.public_key == *public_key)
+                    .ok_or_else(|| "Public key is not authorized".to_string())?;
+                UnparsedPublicKey::new(&ED25519, &key.public_key)
+                    .verify(&AuthMessage::signed_challenge(nonce), signature)
+                    .map_err(|_| format!("Invalid signature for key {:?}", key.comment))?;
+                log::info!("Operator key {:?} verified", key.comment);
+                key.role
+            }
+        };
+
+        if !granted.allows(requested) {
+            return Err(format!(
+                "{granted:?} credentials can't connect as {requested:?}"
+            ));
+        }
+
+        Ok(requested)
+    }
+
+    async fn receive_request(
+        operator_ws: &mut OperatorWebSocket,
+    ) -> GoliathVehicleResult<Result<(Role, AuthProof), String>> {
+        let request = match tokio::time::timeout(AUTH_TIMEOUT, operator_ws.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => match AuthMessage::read_from_json(&text) {
+                Ok(AuthMessage::Request { role, proof }) => Ok((role, proof)),
+                Ok(msg) => Err(format!("Expected an auth request, got {msg:?}")),
+                Err(err) => Err(format!("Invalid auth request: {err}")),
+            },
+            Ok(Some(Ok(msg))) => Err(format!("Expected an auth request, got {msg:?}")),
+            Ok(Some(Err(err))) => return Err(Box::new(err).into()),
+            Ok(None) => {
+                return Err(GoliathVehicleError::AuthenticationError(
+                    "Operator disconnected during authentication".to_string(),
+                ));
+            }
+            Err(_) => Err("Timed out waiting for an auth request".to_string()),
+        };
+
+        Ok(request)
+    }
+
+    /// Challenges the operator and returns the role it was granted along with the session's
+    /// resume token, operators that fail are sent a close frame without the details
+    pub(crate) async fn authenticate(
+        &self,
+        operator_ws: &mut OperatorWebSocket,
+        resume_token: &str,
+    ) -> GoliathVehicleResult<Role> {
+        let mut nonce = vec![0; NONCE_LEN];
+        self.rng.fill(&mut nonce).map_err(|_| {
+            GoliathVehicleError::GeneralError("Failed to generate a challenge".to_string())
+        })?;
+
+        let challenge = AuthMessage::Challenge {
+            nonce: nonce.clone(),
+        };
+        operator_ws
+            .send(Message::Text(challenge.into_json()?.into()))
+            .await
+            .map_err(Box::new)?;
+
+        let result = Self::receive_request(operator_ws)
+            .await?
+            .and_then(|(role, proof)| self.verify(&nonce, role, &proof));
+
+        let role = match result {
+            Ok(role) => role,
+            Err(reason) => {
+                operator_ws
+                    .close(Some(CloseFrame {
+                        code: CloseCode::Policy,
+                        reason: "Authentication failed".into(),
+                    }))
+                    .await
+                    .ok();
+                return Err(GoliathVehicleError::AuthenticationError(reason));
+            }
+        };
+
+        operator_ws
+            .send(Message::Text(
+                AuthMessage::Granted {
+                    role,
+                    resume_token: resume_token.to_string(),
+                }
+                .into_json()?
+                .into(),
+            ))
+            .await
+            .map_err(Box::new)?;
+        log::info!("Operator authenticated as {role:?}");
+
+        Ok(role)
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    const DRIVER_TOKEN: &str = "driver-token-0123456789";
+    const OBSERVER_TOKEN: &str = "observer-token-0123456789";
+    const SAFETY_TOKEN: &str = "safety-token-0123456789";
+    const NONCE: [u8; NONCE_LEN] = [7; NONCE_LEN];
+
+    fn key_pair() -> Ed25519KeyPair {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap()
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    fn authenticator(authorized_keys: &str) -> OperatorAuthenticator {
+        let config = AuthConfig {
+            driver_token: Some(DRIVER_TOKEN.to_string()),
+            observer_token: Some(OBSERVER_TOKEN.to_string()),
+            safety_token: Some(SAFETY_TOKEN.to_string()),
+            authorized_keys_path: None,
+        };
+        OperatorAuthenticator {
+            authorized_keys: OperatorAuthenticator::read_authorized_keys(authorized_keys).unwrap(),
+            ..OperatorAuthenticator::try_new(&config).unwrap()
+        }
+    }
+
+    fn proof(key_pair: &Ed25519KeyPair, nonce: &[u8]) -> AuthProof {
+        AuthProof::Ed25519 {
+            public_key: key_pair.public_key().as_ref().to_vec(),
+            signature: key_pair
+                .sign(&AuthMessage::signed_challenge(nonce))
+                .as_ref()
+                .to_vec(),
+        }
+    }
+
+    fn token(token: &str) -> AuthProof {
+        AuthProof::Token(token.to_string())
+    }
+
+    #[test]
+    fn reads_keys_skipping_comments() {
+        let driver = hex(key_pair().public_key().as_ref());
+        let observer = hex(key_pair().public_key().as_ref());
+        let contents = format!(
+            "# Operators\n\ndriver {driver} laptop  # the main one\n  observer {observer}\n"
+        );
+
+        let keys = OperatorAuthenticator::read_authorized_keys(&contents).unwrap();
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0].role, Role::Driver);
+        assert_eq!(hex(&keys[0].public_key), driver);
+        assert_eq!(keys[0].comment, "laptop");
+        assert_eq!(keys[1].role, Role::Observer);
+        assert_eq!(keys[1].comment, "");
+    }
+
+    #[test]
+    fn rejects_malformed_key_lines() {
+        let key = hex(key_pair().public_key().as_ref());
+        for contents in [
+            format!("admin {key}"),
+            "driver".to_string(),
+            "driver not-hex".to_string(),
+            format!("driver {}", &key[..key.len() - 2]),
+            format!("driver {key}00"),
+            format!("driver {}", &key[..key.len() - 1]),
+        ] {
+            assert!(
+                OperatorAuthenticator::read_authorized_keys(&contents).is_err(),
+                "{contents:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_duplicate_keys() {
+        let key = hex(key_pair().public_key().as_ref());
+        let contents = format!("observer {key}\ndriver {key}\n");
+        assert!(OperatorAuthenticator::read_authorized_keys(&contents).is_err());
+    }
+
+    #[test]
+    fn tokens_grant_their_role() {
+        let authenticator = authenticator("");
+        for (proof, role) in [
+            (token(OBSERVER_TOKEN), Role::Observer),
+            (token(DRIVER_TOKEN), Role::Driver),
+            (token(SAFETY_TOKEN), Role::Safety),
+        ] {
+            assert_eq!(authenticator.verify(&NONCE, role, &proof), Ok(role));
+        }
+        assert!(
+            authenticator
+                .verify(&NONCE, Role::Observer, &token("unknown-token-0123456789"))
+                .is_err()
+        );
+        assert!(
+            authenticator
+                .verify(&NONCE, Role::Observer, &AuthProof::None)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn signed_challenge_grants_the_key_role() {
+        let key_pair = key_pair();
+        let authenticator =
+            authenticator(&format!("driver {}", hex(key_pair.public_key().as_ref())));
+        assert_eq!(
+            authenticator.verify(&NONCE, Role::Driver, &proof(&key_pair, &NONCE)),
+            Ok(Role::Driver)
+        );
+    }
+
+    #[test]
+    fn rejects_a_signature_of_another_nonce() {
+        let key_pair = key_pair();
+        let authenticator =
+            authenticator(&format!("driver {}", hex(key_pair.public_key().as_ref())));
+        let other_nonce = [8; NONCE_LEN];
+        assert!(
+            authenticator
+                .verify(&NONCE, Role::Driver, &proof(&key_pair, &other_nonce))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn rejects_a_signature_by_another_key() {
+        let authorized = key_pair();
+        let other = key_pair();
+        let authenticator =
+            authenticator(&format!("driver {}", hex(authorized.public_key().as_ref())));
+
+        // Unknown key
+        assert!(
+            authenticator
+                .verify(&NONCE, Role::Driver, &proof(&other, &NONCE))
+                .is_err()
+        );
+
+        // Authorized key, signed by the other one
+        let AuthProof::Ed25519 { signature, .. } = proof(&other, &NONCE) else {
+            unreachable!()
+        };
+        let forged = AuthProof::Ed25519 {
+            public_key: authorized.public_key().as_ref().to_vec(),
+            signature,
+        };
+        assert!(authenticator.verify(&NONCE, Role::Driver, &forged).is_err());
+    }
+
+    #[test]
+    fn rejects_roles_above_the_credentials() {
+        let key_pair = key_pair();
+        let authenticator =
+            authenticator(&format!("observer {}", hex(key_pair.public_key().as_ref())));
+        let key_proof = proof(&key_pair, &NONCE);
+
+        for requested in [Role::Driver, Role::Safety] {
+            assert!(authenticator.verify(&NONCE, requested, &key_proof).is_err());
+            assert!(
+                authenticator
+                    .verify(&NONCE, requested, &token(OBSERVER_TOKEN))
+                    .is_err()
+            );
+        }
+        assert!(
+            authenticator
+                .verify(&NONCE, Role::Safety, &token(DRIVER_TOKEN))
+                .is_err()
+        );
+
+        // Asking for less than allowed is fine, and only gets what was asked for
+        assert_eq!(
+            authenticator.verify(&NONCE, Role::Observer, &token(SAFETY_TOKEN)),
+            Ok(Role::Observer)
+        );
+    }
+
+    #[test]
+    fn accepts_anyone_without_auth_configured() {
+        let authenticator = OperatorAuthenticator::try_new(&AuthConfig::default()).unwrap();
+        assert_eq!(
+            authenticator.verify(&NONCE, Role::Safety, &AuthProof::None),
+            Ok(Role::Safety)
+        );
+    }
+}
@@ -0,0 +1,116 @@
+use crate::GoliathVehicleResult;
+use crate::error::GoliathVehicleError;
+use crate::server::OperatorWebSocket;
+use crate::session::{ParkedSession, ParkedSessions};
+use crate::video::streamer::VideoStreamer;
+use futures_util::SinkExt;
+use futures_util::stream::StreamExt;
+use goliath_common::{Capability, Hello, Negotiated, WireFormat};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const VEHICLE_CAPABILITIES: [Capability; 3] = [
+    Capability::Turret,
+    Capability::Telemetry,
+    Capability::VideoH264,
+];
+
+// The operator picks, JSON is only expected while debugging
+const VEHICLE_WIRE_FORMATS: [WireFormat; 2] = [WireFormat::Bitcode, WireFormat::Json];
+
+pub(crate) enum Handshake {
+    New(Negotiated),
+    Resumed(ParkedSession), // Keeps what was negotiated the first time
+}
+
+/// Waits for the operator's hello and answers with what was negotiated, incompatible operators
+/// are sent a close frame with the reason
+pub(crate) async fn accept_handshake(
+    operator_ws: &mut OperatorWebSocket,
+    parked_sessions: &ParkedSessions,
+    video: &VideoStreamer,
+) -> GoliathVehicleResult<Handshake> {
+    let local_hello = Hello::new(VEHICLE_CAPABILITIES.to_vec(), VEHICLE_WIRE_FORMATS.to_vec());
+
+    let negotiated = match tokio::time::timeout(HANDSHAKE_TIMEOUT, operator_ws.next()).await {
+        Ok(Some(Ok(Message::Text(text)))) => Hello::read_from_json(&text).map_err(|err| {
+            log::warn!("Failed to read operator hello: {err}");
+            "Invalid hello".to_string()
+        }),
+        Ok(Some(Ok(msg))) => Err(format!("Expected a hello, got {msg:?}")),
+        Ok(Some(Err(err))) => return Err(Box::new(err).into()),
+        Ok(None) => {
+            return Err(GoliathVehicleError::HandshakeError(
+                "Operator disconnected during the handshake".to_string(),
+            ));
+        }
+        Err(_) => Err("Timed out waiting for a hello".to_string()),
+    }
+    .and_then(|peer_hello| {
+        let mut negotiated = local_hello.negotiate(&peer_hello)?;
+        if negotiated.capabilities.contains(&Capability::VideoH264)
+            && let Some(offer) = &peer_hello.video_offer
+        {
+            // The operator can still drive without the video
+            match video.accept(offer) {
+                Ok(transport) => negotiated.video = Some(transport),
+                Err(reason) => log::warn!("Not sending the video to the operator: {reason}"),
+            }
+        }
+        log::info!(
+            "Operator {:?} connected, negotiated {negotiated:?}",
+            peer_hello.build
+        );
+        Ok((negotiated, peer_hello.resume_token))
+    });
+
+    let (negotiated, resume_token) = match negotiated {
+        Ok(negotiated) => negotiated,
+        Err(reason) => {
+            operator_ws
+                .close(Some(CloseFrame {
+                    code: CloseCode::Protocol,
+                    reason: reason.clone().into(),
+                }))
+                .await
+                .ok();
+            return Err(GoliathVehicleError::HandshakeError(reason));
+        }
+    };
+
+    // An unknown or expired token isn't an error, the operator is treated as a new one
+    let resumed = resume_token.and_then(|resume_token| parked_sessions.resume(&resume_token));
+    let reply = match &resumed {
+        Some(parked) => {
+            Hello::reply(&parked.negotiated).with_resume_token(Some(parked.resume_token.clone()))
+        }
+        None => Hello::reply(&negotiated),
+    };
+
+    if let Err(err) = send_reply(operator_ws, reply).await {
+        // Still resumable by a later connection
+        if let Some(parked) = resumed {
+            parked_sessions.park(parked);
+        }
+        return Err(err);
+    }
+
+    Ok(match resumed {
+        Some(parked) => Handshake::Resumed(parked),
+        None => Handshake::New(negotiated),
+    })
+}
+
+async fn send_reply(operator_ws: &mut OperatorWebSocket, reply: Hello) -> GoliathVehicleResult<()> {
+    let sending = operator_ws.send(Message::Text(reply.into_json()?.into()));
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, sending).await {
+        Ok(result) => result.map_err(|err| Box::new(err).into()),
+        Err(_) => Err(GoliathVehicleError::HandshakeError(
+            "Timed out sending the hello".to_string(),
+        )),
+    }
+}
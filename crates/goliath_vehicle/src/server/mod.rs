@@ -1,31 +1,194 @@
 use crate::GoliathVehicleResult;
+use crate::config::VehicleConfig;
+use crate::context::VehicleContext;
+use crate::error::GoliathVehicleError;
+use crate::server::auth::OperatorAuthenticator;
+use crate::server::handshake::{Handshake, accept_handshake};
+use crate::server::tls::OperatorTlsAcceptor;
 use crate::session::GoliathVehicleSession;
-use crate::video::capture_pipeline::ZedCamCaps;
-use crate::video::encoding_pipeline::EncoderType;
-use jetgpio::Gpio;
+use goliath_common::{MAX_MESSAGE_SIZE, Role};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::sync::Arc;
-use tokio::net::TcpListener;
-use tokio_tungstenite::{MaybeTlsStream, accept_async};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tokio_tungstenite::{WebSocketStream, accept_async_with_config};
+
+mod auth;
+mod handshake;
+mod tls;
+
+// For the TLS and websocket upgrade, the handshake and authentication have their own timeouts
+const UPGRADE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Transport under the operator's websocket, plain TCP or TLS depending on the config
+pub(crate) trait OperatorIo: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> OperatorIo for T {}
+
+pub(crate) type OperatorWebSocket = WebSocketStream<Box<dyn OperatorIo>>;
 
 pub(crate) struct GoliathServer {
     listener: TcpListener,
+    tls_acceptor: Option<OperatorTlsAcceptor>,
+    authenticator: OperatorAuthenticator,
+    connections: Arc<Semaphore>, // One permit per operator
+    // One more operator once the others are all connected, only kept by a safety operator
+    safety_connection: Arc<Semaphore>,
+    next_session_id: AtomicU64,
 }
 
 impl GoliathServer {
-    pub(crate) async fn await_connection(
-        &mut self,
-        gpio: Arc<Gpio>,
-    ) -> GoliathVehicleResult<GoliathVehicleSession> {
-        let (new_connection, addr) = self.listener.accept().await?;
-        // TODO: Move to TlsStream
-        let ws_conn = accept_async(MaybeTlsStream::Plain(new_connection))
+    /// Accepts operators until the listener fails, each one in its own task
+    pub(crate) async fn run(
+        self: Arc<Self>,
+        context: Arc<VehicleContext>,
+    ) -> GoliathVehicleResult<()> {
+        loop {
+            log::info!("Awaiting new connection");
+            let (new_connection, addr) = self.listener.accept().await?;
+
+            let server = Arc::clone(&self);
+            let context = Arc::clone(&context);
+            tokio::spawn(async move {
+                // A rejected operator must not take the vehicle or the other operators down
+                match server.serve_operator(new_connection, addr, context).await {
+                    Ok(()) => log::info!("Operator {addr} disconnected"),
+                    Err(err) => log::warn!("Connection from {addr} failed: {err}"),
+                }
+            });
+        }
+    }
+
+    async fn serve_operator(
+        &self,
+        stream: TcpStream,
+        addr: SocketAddr,
+        context: Arc<VehicleContext>,
+    ) -> GoliathVehicleResult<()> {
+        // Held until the session ends, whether the reserved one is used is known from the start
+        let permit = match Arc::clone(&self.connections).try_acquire_owned() {
+            Ok(permit) => Some((permit, false)),
+            Err(_) => Arc::clone(&self.safety_connection)
+                .try_acquire_owned()
+                .ok()
+                .map(|permit| (permit, true)),
+        };
+
+        let mut ws_conn = tokio::time::timeout(UPGRADE_TIMEOUT, self.upgrade(stream))
+            .await
+            .map_err(|_| {
+                GoliathVehicleError::HandshakeError(
+                    "Timed out upgrading the connection".to_string(),
+                )
+            })??;
+
+        let Some((_permit, reserved)) = permit else {
+            return Err(Self::reject_busy(&mut ws_conn).await);
+        };
+
+        let mut session =
+            match accept_handshake(&mut ws_conn, &context.parked_sessions, &context.video).await? {
+                Handshake::Resumed(parked) => {
+                    if reserved && parked.role != Role::Safety {
+                        context.parked_sessions.park(parked);
+                        return Err(Self::reject_busy(&mut ws_conn).await);
+                    }
+                    GoliathVehicleSession::resume(parked, addr, ws_conn, context)?
+                }
+                Handshake::New(negotiated) => {
+                    let resume_token = context.parked_sessions.new_token()?;
+                    let role = self
+                        .authenticator
+                        .authenticate(&mut ws_conn, &resume_token)
+                        .await?;
+                    if reserved && role != Role::Safety {
+                        return Err(Self::reject_busy(&mut ws_conn).await);
+                    }
+
+                    let session_id = self.next_session_id.fetch_add(1, Ordering::Relaxed);
+                    GoliathVehicleSession::try_new(
+                        session_id,
+                        resume_token,
+                        addr,
+                        ws_conn,
+                        negotiated,
+                        role,
+                        context,
+                    )?
+                }
+            };
+        session.run().await
+    }
+
+    async fn upgrade(&self, stream: TcpStream) -> GoliathVehicleResult<OperatorWebSocket> {
+        let stream: Box<dyn OperatorIo> = match &self.tls_acceptor {
+            Some(tls_acceptor) => Box::new(tls_acceptor.accept(stream).await?),
+            None => Box::new(stream),
+        };
+
+        // Oversized frames are refused before being buffered
+        let ws_config = WebSocketConfig::default()
+            .max_message_size(Some(MAX_MESSAGE_SIZE))
+            .max_frame_size(Some(MAX_MESSAGE_SIZE));
+        let ws_conn = accept_async_with_config(stream, Some(ws_config))
             .await
             .map_err(Box::new)?;
-        GoliathVehicleSession::try_new(addr, ws_conn, ZedCamCaps::NOHD15, EncoderType::V4L2, gpio)
+        Ok(ws_conn)
+    }
+
+    async fn reject_busy(ws_conn: &mut OperatorWebSocket) -> GoliathVehicleError {
+        let frame = CloseFrame {
+            code: CloseCode::Again,
+            reason: "Too many operators connected".into(),
+        };
+        ws_conn.close(Some(frame)).await.ok();
+        GoliathVehicleError::GeneralError("Too many operators connected".to_string())
+    }
+
+    pub(crate) async fn try_new(config: &VehicleConfig) -> GoliathVehicleResult<Self> {
+        let port = config.server.port;
+        let tls_acceptor = config
+            .tls
+            .as_ref()
+            .map(OperatorTlsAcceptor::try_new)
+            .transpose()?;
+        if tls_acceptor.is_none() {
+            log::warn!("TLS is not configured, the control websocket is unencrypted");
+        }
+
+        let authenticator = OperatorAuthenticator::try_new(&config.auth)?;
+
+        let listener = match Self::bind_dual_stack(port) {
+            Ok(listener) => listener,
+            Err(err) => {
+                log::warn!("Failed to listen on IPv6 ({err}), only accepting IPv4 operators");
+                TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await?
+            }
+        };
+        Ok(Self {
+            listener,
+            tls_acceptor,
+            authenticator,
+            connections: Arc::new(Semaphore::new(config.server.max_connections)),
+            safety_connection: Arc::new(Semaphore::new(1)),
+            next_session_id: AtomicU64::new(1),
+        })
     }
 
-    pub(crate) async fn try_new(port: usize) -> GoliathVehicleResult<Self> {
-        let listener = TcpListener::bind(format!("0.0.0.0:{port}")).await?;
-        Ok(Self { listener })
+    /// Accepts both IPv6 and IPv4 operators on a single socket, whatever the system default is
+    fn bind_dual_stack(port: u16) -> GoliathVehicleResult<TcpListener> {
+        let socket = Socket::new(Domain::IPV6, Type::STREAM, Some(Protocol::TCP))?;
+        socket.set_only_v6(false)?;
+        socket.set_reuse_address(true)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SocketAddr::from((Ipv6Addr::UNSPECIFIED, port)).into())?;
+        socket.listen(128)?;
+        Ok(TcpListener::from_std(socket.into())?)
     }
 }
@@ -0,0 +1,70 @@
+use crate::GoliathVehicleResult;
+use crate::config::TlsConfig;
+use crate::error::GoliathVehicleError;
+use rustls::RootCertStore;
+use rustls::crypto::ring::default_provider;
+use rustls::server::WebPkiClientVerifier;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+
+// A client that never finishes the TLS handshake must not hold up the accept loop
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(crate) struct OperatorTlsAcceptor {
+    acceptor: TlsAcceptor,
+    mutual: bool,
+}
+
+impl OperatorTlsAcceptor {
+    pub(crate) fn try_new(config: &TlsConfig) -> GoliathVehicleResult<Self> {
+        let provider = Arc::new(default_provider());
+        let certs =
+            CertificateDer::pem_file_iter(&config.cert_path)?.collect::<Result<Vec<_>, _>>()?;
+        let key = PrivateKeyDer::from_pem_file(&config.key_path)?;
+
+        let builder = rustls::ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()?;
+        let builder = match &config.client_ca_path {
+            Some(client_ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in CertificateDer::pem_file_iter(client_ca_path)? {
+                    roots.add(cert?)?;
+                }
+                let verifier =
+                    WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                        .build()
+                        .map_err(|err| GoliathVehicleError::ConfigError(err.to_string()))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let server_config = builder.with_single_cert(certs, key)?;
+
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+            mutual: config.client_ca_path.is_some(),
+        })
+    }
+
+    /// Fails if the operator doesn't complete the handshake, or doesn't present a trusted
+    /// certificate when mutual TLS is enabled
+    pub(crate) async fn accept(
+        &self,
+        stream: TcpStream,
+    ) -> GoliathVehicleResult<TlsStream<TcpStream>> {
+        let tls_stream = tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, self.acceptor.accept(stream))
+            .await
+            .map_err(|_| GoliathVehicleError::HandshakeError("TLS handshake timed out".into()))??;
+
+        if self.mutual {
+            log::info!("Operator authenticated with a client certificate");
+        }
+
+        Ok(tls_stream)
+    }
+}
@@ -1,85 +1,448 @@
 use crate::GoliathVehicleResult;
+use crate::context::VehicleContext;
 use crate::error::GoliathVehicleError;
-use crate::motors::MotorsContoller;
-use crate::video::capture_pipeline::{CapturePipeline, ZedCamCaps};
-use crate::video::encoding_pipeline::{EncoderType, EncodingPipline};
-use crate::video::rtp_pipeline::RTPPipeline;
+use crate::lease::LeaseHolder;
+use crate::server::OperatorWebSocket;
+use crate::session::validation::CommandValidator;
+use crate::telemetry::{read_cpu_load, read_cpu_temperature};
+use futures_util::SinkExt;
 use futures_util::stream::StreamExt;
-pub use goliath_common::{GoliathCommand, MotorCommand};
-use goliath_common::{GoliathGstPipeline, stop_main_loop};
-use jetgpio::Gpio;
+use goliath_common::{
+    Capability, CommandRejection, Delivery, EncodedMessage, GoliathCodec, GoliathMessage,
+    GoliathPayload, LinkStats, MessageFilter, MessageStamper, MotorsStatus, Negotiated, Role,
+    SessionClock, TelemetryReport, TrackTelemetry, VideoTransport, WireFormat, codec_for_format,
+};
+pub use goliath_common::{GoliathCommand, GoliathReport, MotorCommand, MotorReport};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::thread;
-use tokio::net::TcpStream;
-use tokio::sync::mpsc;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, watch};
 use tokio_tungstenite::tungstenite::Message;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
 
-pub(crate) struct GoliathVehicleSession {
-    operator_ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
+mod resume;
+mod validation;
+
+pub(crate) use resume::{ParkedSession, ParkedSessions};
 
-    capture_pipeline: Arc<CapturePipeline>,
+// Number of ping/pong exchanges the link statistics are computed over
+const LINK_STATS_WINDOW: usize = 16;
+
+pub(crate) struct GoliathVehicleSession {
+    id: u64,
+    resume_token: String,
+    label: String, // Shown to the other operators while holding the lease
+    role: Role,
+    operator_ws: OperatorWebSocket,
+    codec: Box<dyn GoliathCodec>,
+    command_validator: CommandValidator,
+    clock: SessionClock,
+    message_filter: MessageFilter,
+    message_stamper: MessageStamper,
+    link_stats: LinkStats,
+    context: Arc<VehicleContext>,
+    emergency_stop_rx: watch::Receiver<bool>,
+    lease_rx: watch::Receiver<Option<LeaseHolder>>,
+    motors_report_rx: broadcast::Receiver<MotorReport>,
+    video: Option<VideoTransport>, // Only if the operator negotiated the video
+    video_addr: Option<SocketAddr>, // Where the stream goes, with the operator's port
+    video_registered: bool,        // Already being sent the stream
 
-    motors_cmd_tx: mpsc::Sender<MotorCommand>,
-    motors_thread: Option<thread::JoinHandle<GoliathVehicleResult<()>>>,
+    capabilities: Vec<Capability>,
+    started: Instant,
 }
 
 impl GoliathVehicleSession {
     pub(crate) fn try_new(
+        id: u64,
+        resume_token: String,
         operator_addr: SocketAddr,
-        operator_ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
-        capture_caps: ZedCamCaps,
-        encoder_type: EncoderType,
-        gpio: Arc<Gpio>,
+        operator_ws: OperatorWebSocket,
+        negotiated: Negotiated,
+        role: Role,
+        context: Arc<VehicleContext>,
     ) -> GoliathVehicleResult<Self> {
-        let ip = match operator_addr {
-            SocketAddr::V4(ip) => ip.ip().to_string(),
-            SocketAddr::V6(_) => {
-                return Err(GoliathVehicleError::GeneralError(
-                    "IPV6 is not supported".to_string(),
-                ));
-            }
-        };
-        let rtp_pipeline = Arc::new(RTPPipeline::try_new(vec![(ip, 8000)])?);
-        let encoding_pipeline = Arc::new(EncodingPipline::try_new(encoder_type, rtp_pipeline)?);
-        let capture_pipeline = Arc::new(CapturePipeline::try_new(capture_caps, encoding_pipeline)?);
-
-        let (motors_cmd_tx, motors_cmd_rx) = mpsc::channel::<MotorCommand>(32);
-        let motors_thread = thread::Builder::new()
-            .name("MotorsThread".to_string())
-            .spawn({
-                let mut motors = MotorsContoller::try_new(gpio)?;
-                move || motors.run_thread(motors_cmd_rx)
-            })?;
+        let config = &context.config;
+        let capabilities = negotiated.capabilities;
+
+        // IPv4 operators reach the dual-stack listener as IPv4-mapped IPv6 addresses
+        let video = negotiated.video;
+        let video_addr = video
+            .as_ref()
+            .map(|video| SocketAddr::new(operator_addr.ip().to_canonical(), video.port));
+
+        let clock = SessionClock::new();
+
+        // The operator is told the current state as soon as the session starts
+        let mut emergency_stop_rx = context.emergency_stop.subscribe();
+        emergency_stop_rx.mark_changed();
+        let mut lease_rx = context.lease.subscribe();
+        lease_rx.mark_changed();
 
         Ok(Self {
+            id,
+            resume_token,
+            label: format!("{role:?} operator at {operator_addr}"),
+            role,
             operator_ws,
+            codec: codec_for_format(negotiated.wire_format),
+            command_validator: CommandValidator::new(
+                config.commands.clone(),
+                config.motors.turret.min_angle..=config.motors.turret.max_angle,
+                Arc::clone(&context.emergency_stop),
+                capabilities.contains(&Capability::Turret),
+                role,
+            ),
+            clock,
+            message_filter: MessageFilter::new(
+                clock,
+                Duration::from_millis(config.commands.max_age_ms),
+            ),
+            message_stamper: MessageStamper::new(clock),
+            link_stats: LinkStats::new(LINK_STATS_WINDOW),
+            emergency_stop_rx,
+            lease_rx,
+            motors_report_rx: context.motors.report_tx.subscribe(),
+            video,
+            video_addr,
+            video_registered: false,
 
-            capture_pipeline,
-
-            motors_cmd_tx,
-            motors_thread: Some(motors_thread),
+            context,
+            capabilities,
+            started: Instant::now(),
         })
     }
 
-    async fn handle_command(&mut self, cmd: GoliathCommand) -> GoliathVehicleResult<()> {
-        match cmd {
-            GoliathCommand::Motor(motor_cmd) => {
-                self.motors_cmd_tx
-                    .send(motor_cmd)
-                    .await
-                    .map_err(|err| GoliathVehicleError::TokioSendError(err.to_string()))?;
+    /// Picks a parked session back up for its reconnected operator
+    pub(crate) fn resume(
+        parked: ParkedSession,
+        operator_addr: SocketAddr,
+        operator_ws: OperatorWebSocket,
+        context: Arc<VehicleContext>,
+    ) -> GoliathVehicleResult<Self> {
+        log::info!("Resuming session #{} for {}", parked.id, parked.label);
+        let mut session = match Self::try_new(
+            parked.id,
+            parked.resume_token.clone(),
+            operator_addr,
+            operator_ws,
+            parked.negotiated.clone(),
+            parked.role,
+            Arc::clone(&context),
+        ) {
+            Ok(session) => session,
+            Err(err) => {
+                discard_parked(&parked, &context);
+                return Err(err);
             }
+        };
+        session.label = parked.label;
+
+        // The operator may have come back from another address
+        if session.video_addr == parked.video_addr {
+            session.video_registered = true;
+        } else if let Some(addr) = parked.video_addr {
+            context.video.remove_client(addr);
+        }
+
+        Ok(session)
+    }
+
+    /// Keeps the lease and the video going for the operator to resume, until the grace ends
+    fn park(&mut self, grace: Duration) {
+        let parked_at = Instant::now();
+        self.context.lease.park(self.id, grace);
+        self.context.parked_sessions.park(ParkedSession {
+            id: self.id,
+            resume_token: self.resume_token.clone(),
+            label: self.label.clone(),
+            role: self.role,
+            negotiated: Negotiated {
+                capabilities: self.capabilities.clone(),
+                wire_format: self.codec.format(),
+                video: self.video.clone(),
+            },
+            video_addr: self.video_addr.filter(|_| self.video_registered),
+            parked_at,
+        });
+        log::info!("Session #{} parked for {grace:?}", self.id);
+
+        let context = Arc::clone(&self.context);
+        let resume_token = self.resume_token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(grace).await;
+            if let Some(parked) = context.parked_sessions.expire(&resume_token, parked_at) {
+                log::info!("Session #{} was not resumed in time", parked.id);
+                discard_parked(&parked, &context);
+            }
+        });
+    }
+
+    async fn handle_message(&mut self, msg: GoliathMessage) -> GoliathVehicleResult<()> {
+        let received_us = self.clock.now_us();
+        let delivery = self.message_filter.check(&msg);
+        let applied = match msg.payload {
+            GoliathPayload::Ping => {
+                return self
+                    .send_payload(GoliathPayload::Pong {
+                        ping_timestamp_us: msg.timestamp_us,
+                        ping_received_us: received_us,
+                    })
+                    .await;
+            }
+            GoliathPayload::Pong {
+                ping_timestamp_us,
+                ping_received_us,
+            } => {
+                self.link_stats.record(
+                    ping_timestamp_us,
+                    ping_received_us,
+                    msg.timestamp_us,
+                    received_us,
+                );
+                return match self.link_stats.report() {
+                    Some(link) => self.send_report(GoliathReport::LinkHealth(link)).await,
+                    None => Ok(()),
+                };
+            }
+            // Applying these late would only fight the newer commands
+            GoliathPayload::Command(cmd) if cmd.is_control() && delivery != Delivery::Fresh => {
+                log::debug!("Discarding {delivery:?} command #{}: {cmd:?}", msg.seq);
+                false
+            }
+            GoliathPayload::Command(cmd) => {
+                log::info!("Got command: {cmd:?}");
+                self.handle_command(cmd).await?
+            }
+            payload => {
+                log::warn!("Got unexpected payload from operator: {payload:?}");
+                false
+            }
+        };
+
+        if msg.ack_requested {
+            self.send_payload(GoliathPayload::Ack {
+                seq: msg.seq,
+                applied,
+            })
+            .await?;
         }
         Ok(())
     }
 
+    /// Returns whether the command was applied
+    async fn handle_command(&mut self, cmd: GoliathCommand) -> GoliathVehicleResult<bool> {
+        let cmd = match self.command_validator.validate(&cmd) {
+            Ok(cmd) => cmd,
+            Err(reason) => return self.reject(&cmd, reason).await,
+        };
+
+        // Any command from the holder keeps the lease alive
+        if cmd.needs_lease() && !self.context.lease.renew(self.id) {
+            // The watchdog is fed by the holder only, an observer's heartbeats are harmless
+            if cmd == GoliathCommand::Heartbeat {
+                return Ok(false);
+            }
+            return self.reject(&cmd, CommandRejection::NoDrivingLease).await;
+        }
+
+        match cmd {
+            GoliathCommand::Motor(motor_cmd) => self.send_motors(motor_cmd).await?,
+            GoliathCommand::Heartbeat => self.send_motors(MotorCommand::Heartbeat).await?,
+            // The motors thread picks the latch up on its next tick
+            GoliathCommand::EmergencyStop => {
+                if self.context.emergency_stop.engage() {
+                    log::warn!("Emergency stop engaged by {}", self.label);
+                }
+            }
+            GoliathCommand::ClearEmergencyStop => {
+                if self.context.emergency_stop.clear() {
+                    log::warn!("Emergency stop cleared by {}", self.label);
+                }
+            }
+            GoliathCommand::TakeControl => {
+                let was_holder = self.context.lease.is_held_by(self.id);
+                if let Err(holder) = self.context.lease.take(self.id, &self.label, self.role) {
+                    return self
+                        .reject(&cmd, CommandRejection::LeaseHeld { holder })
+                        .await;
+                }
+
+                // Whatever the previous holder left the vehicle doing is not this operator's intent
+                if !was_holder {
+                    self.stop_motion().await?;
+                }
+            }
+            GoliathCommand::ReleaseControl => {
+                if !self.context.lease.release(self.id) {
+                    return Ok(false);
+                }
+                self.stop_motion().await?;
+            }
+        }
+        Ok(true)
+    }
+
+    async fn reject(
+        &mut self,
+        cmd: &GoliathCommand,
+        reason: CommandRejection,
+    ) -> GoliathVehicleResult<bool> {
+        log::warn!("Rejected command {cmd:?} from {}: {reason:?}", self.label);
+        self.send_report(GoliathReport::CommandRejected {
+            command: format!("{cmd:?}"),
+            reason,
+        })
+        .await?;
+        Ok(false)
+    }
+
+    async fn send_motors(&mut self, cmd: MotorCommand) -> GoliathVehicleResult<()> {
+        self.context
+            .motors
+            .cmd_tx
+            .send(cmd)
+            .await
+            .map_err(|err| GoliathVehicleError::TokioSendError(err.to_string()))
+    }
+
+    async fn stop_motion(&mut self) -> GoliathVehicleResult<()> {
+        self.send_motors(MotorCommand::Drive {
+            thrust: 0.0,
+            steer: 0.0,
+        })
+        .await?;
+        self.send_motors(MotorCommand::TurretRate(0.0)).await
+    }
+
+    fn lease_report(&mut self) -> GoliathReport {
+        let holder = self.lease_rx.borrow_and_update();
+        GoliathReport::DrivingLease {
+            holder: holder.as_ref().map(|holder| holder.label.clone()),
+            yours: holder
+                .as_ref()
+                .is_some_and(|holder| holder.session_id == self.id),
+        }
+    }
+
+    fn collect_telemetry(&self) -> TelemetryReport {
+        let motors_state = *self.context.motors.state_rx.borrow();
+        let track_telemetry = |power: f32| TrackTelemetry {
+            power: power.abs(),
+            forward: power >= 0.0,
+        };
+
+        let motors = if self.context.motors.is_running() {
+            MotorsStatus::Running
+        } else {
+            MotorsStatus::Stopped
+        };
+
+        TelemetryReport {
+            left_track: track_telemetry(motors_state.left_power),
+            right_track: track_telemetry(motors_state.right_power),
+            turret_angle: motors_state.turret_angle,
+            uptime_ms: self.started.elapsed().as_millis() as u64,
+            motors,
+            video: self.context.video.state(),
+            cpu_temperature: read_cpu_temperature(&self.context.config.telemetry),
+            cpu_load: read_cpu_load(),
+        }
+    }
+
+    async fn send_report(&mut self, report: GoliathReport) -> GoliathVehicleResult<()> {
+        self.send_payload(GoliathPayload::Report(report)).await
+    }
+
+    async fn send_payload(&mut self, payload: GoliathPayload) -> GoliathVehicleResult<()> {
+        // The operator has nothing to acknowledge
+        let msg = self.message_stamper.stamp(payload, false);
+        let frame = match self.codec.encode(&msg)? {
+            EncodedMessage::Binary(bytes) => Message::Binary(bytes),
+            EncodedMessage::Text(text) => Message::Text(text.into()),
+        };
+        self.operator_ws
+            .send(frame)
+            .await
+            .map_err(|err| Box::new(err).into())
+    }
+
+    fn decode_message(&self, data: &[u8]) -> Option<GoliathMessage> {
+        self.codec
+            .decode(data)
+            .inspect_err(|err| log::error!("Failed to read message: {err} (message: {data:?})"))
+            .ok()
+    }
+
     pub(crate) async fn run(&mut self) -> GoliathVehicleResult<()> {
-        log::info!("Starting Session");
-        self.capture_pipeline.start_pipeline(None)?;
+        log::info!("Starting session #{} for {}", self.id, self.label);
+        if !self.video_registered
+            && let Some(addr) = self.video_addr
+        {
+            self.context.video.add_client(addr)?;
+            self.video_registered = true;
+        }
+        let mut closed_by_operator = false;
+
+        let telemetry_config = &self.context.config.telemetry;
+        let mut telemetry_interval =
+            tokio::time::interval(Duration::from_millis(telemetry_config.interval_ms));
+        let mut ping_interval =
+            tokio::time::interval(Duration::from_millis(telemetry_config.ping_interval_ms));
+        loop {
+            let msg = tokio::select! {
+                maybe_msg = self.operator_ws.next() => match maybe_msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+                report = self.motors_report_rx.recv() => {
+                    let report = match report {
+                        Ok(report) => report,
+                        Err(RecvError::Lagged(missed)) => {
+                            log::warn!("Missed {missed} motor reports");
+                            continue;
+                        }
+                        Err(RecvError::Closed) => break,
+                    };
+                    if let Err(err) = self.send_report(GoliathReport::Motor(report)).await {
+                        log::error!("Failed to send report: {err}");
+                        break;
+                    }
+                    continue;
+                }
+                _ = telemetry_interval.tick(), if self.capabilities.contains(&Capability::Telemetry) => {
+                    let telemetry = self.collect_telemetry();
+                    if let Err(err) = self.send_report(GoliathReport::Telemetry(telemetry)).await {
+                        log::error!("Failed to send report: {err}");
+                        break;
+                    }
+                    continue;
+                }
+                _ = ping_interval.tick() => {
+                    if let Err(err) = self.send_payload(GoliathPayload::Ping).await {
+                        log::error!("Failed to send ping: {err}");
+                        break;
+                    }
+                    continue;
+                }
+                Ok(()) = self.emergency_stop_rx.changed() => {
+                    let engaged = *self.emergency_stop_rx.borrow_and_update();
+                    if let Err(err) = self.send_report(GoliathReport::EmergencyStop { engaged }).await {
+                        log::error!("Failed to send report: {err}");
+                        break;
+                    }
+                    continue;
+                }
+                Ok(()) = self.lease_rx.changed() => {
+                    let report = self.lease_report();
+                    if let Err(err) = self.send_report(report).await {
+                        log::error!("Failed to send report: {err}");
+                        break;
+                    }
+                    continue;
+                }
+            };
 
-        while let Some(msg) = self.operator_ws.next().await {
             match msg {
                 Ok(Message::Ping(_) | Message::Pong(_)) => continue,
                 Ok(Message::Close(frame)) => {
@@ -88,23 +451,31 @@ impl GoliathVehicleSession {
                     } else {
                         log::info!("Got close message from websocket with unknown reason");
                     }
+                    closed_by_operator = true;
                     break;
                 }
-                Ok(Message::Text(text)) => {
-                    log::warn!("Got unexpected text: {text}")
-                }
-                Ok(Message::Binary(bytes)) => match GoliathCommand::read_from_bytes(&bytes) {
-                    Ok(cmd) => {
-                        log::info!("Got command: {cmd:?}");
-                        if let Err(err) = self.handle_command(cmd).await {
-                            log::error!("Failed to handle command: {err}");
-                            break;
-                        }
+                Ok(Message::Text(text)) if self.codec.format() == WireFormat::Json => {
+                    if let Some(msg) = self.decode_message(text.as_bytes())
+                        && let Err(err) = self.handle_message(msg).await
+                    {
+                        log::error!("Failed to handle message: {err}");
+                        break;
                     }
-                    Err(err) => {
-                        log::error!("Failed to read command: {err} (command: {:?})", bytes);
+                }
+                Ok(Message::Binary(bytes)) if self.codec.format() == WireFormat::Bitcode => {
+                    if let Some(msg) = self.decode_message(&bytes)
+                        && let Err(err) = self.handle_message(msg).await
+                    {
+                        log::error!("Failed to handle message: {err}");
+                        break;
                     }
-                },
+                }
+                Ok(Message::Text(_) | Message::Binary(_)) => {
+                    log::warn!(
+                        "Got a frame not matching the {:?} wire format, ignoring",
+                        self.codec.format()
+                    );
+                }
                 Ok(Message::Frame(_)) => {
                     log::debug!("Got raw frame message, ignoring");
                 }
@@ -115,28 +486,32 @@ impl GoliathVehicleSession {
             }
         }
 
-        // Client disconnected, stop everything
-        self.motors_cmd_tx
-            .send(MotorCommand::Thrust(0.0))
-            .await
-            .map_err(|err| GoliathVehicleError::TokioSendError(err.to_string()))?;
-        self.motors_cmd_tx
-            .send(MotorCommand::Steer(0.0))
-            .await
-            .map_err(|err| GoliathVehicleError::TokioSendError(err.to_string()))?;
-        self.motors_cmd_tx
-            .send(MotorCommand::End)
-            .await
-            .map_err(|err| GoliathVehicleError::TokioSendError(err.to_string()))?;
-
-        log::info!("Stopped pipeline");
-        if let Some(motors_thread) = self.motors_thread.take() {
-            log::info!("Joining motors thread");
-            motors_thread.join().ok();
+        // The vehicle keeps running for the other operators, only what this one drove stops
+        let held_lease = self.context.lease.is_held_by(self.id);
+        let grace = Duration::from_millis(self.context.config.server.resume_grace_ms);
+        if closed_by_operator || grace.is_zero() {
+            if self.video_registered
+                && let Some(addr) = self.video_addr
+            {
+                self.context.video.remove_client(addr);
+            }
+            self.context.lease.release(self.id);
+            log::info!("Session #{} ended", self.id);
+        } else {
+            self.park(grace);
         }
 
-        self.capture_pipeline.stop_pipeline().ok();
-        stop_main_loop();
+        if held_lease {
+            self.stop_motion().await?;
+        }
         Ok(())
     }
 }
+
+/// Frees what a parked session was still holding on to
+fn discard_parked(parked: &ParkedSession, context: &VehicleContext) {
+    if let Some(addr) = parked.video_addr {
+        context.video.remove_client(addr);
+    }
+    context.lease.release(parked.id);
+}
@@ -0,0 +1,154 @@
+use crate::error::{GoliathVehicleError, GoliathVehicleResult};
+use goliath_common::{Negotiated, Role};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const RESUME_TOKEN_LEN: usize = 32;
+
+/// What is left of a session whose operator dropped, until it comes back or the grace ends
+pub(crate) struct ParkedSession {
+    pub(crate) id: u64,
+    pub(crate) resume_token: String,
+    pub(crate) label: String,
+    pub(crate) role: Role,
+    pub(crate) negotiated: Negotiated,
+    pub(crate) video_addr: Option<SocketAddr>, // Still sent the video stream
+    pub(crate) parked_at: Instant,
+}
+
+/// Sessions waiting to be resumed, by resume token
+pub(crate) struct ParkedSessions {
+    sessions: Mutex<HashMap<String, ParkedSession>>,
+    rng: SystemRandom,
+}
+
+impl ParkedSessions {
+    pub(crate) fn new() -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            rng: SystemRandom::new(),
+        }
+    }
+
+    /// Random, the token alone is enough to resume a session without authenticating
+    pub(crate) fn new_token(&self) -> GoliathVehicleResult<String> {
+        let mut token = [0; RESUME_TOKEN_LEN];
+        self.rng.fill(&mut token).map_err(|_| {
+            GoliathVehicleError::GeneralError("Failed to generate a resume token".to_string())
+        })?;
+        Ok(token.iter().map(|byte| format!("{byte:02x}")).collect())
+    }
+
+    pub(crate) fn park(&self, session: ParkedSession) {
+        self.lock().insert(session.resume_token.clone(), session);
+    }
+
+    /// Removes the session, so it can only be resumed once
+    pub(crate) fn resume(&self, resume_token: &str) -> Option<ParkedSession> {
+        self.lock().remove(resume_token)
+    }
+
+    /// Removes the session if it is still the one parked at `parked_at`, it may have been
+    /// resumed and parked again since
+    pub(crate) fn expire(&self, resume_token: &str, parked_at: Instant) -> Option<ParkedSession> {
+        let mut sessions = self.lock();
+        match sessions.get(resume_token) {
+            Some(session) if session.parked_at == parked_at => sessions.remove(resume_token),
+            _ => None,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, ParkedSession>> {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goliath_common::WireFormat;
+    use std::time::Duration;
+
+    fn parked(sessions: &ParkedSessions, parked_at: Instant) -> ParkedSession {
+        ParkedSession {
+            id: 1,
+            resume_token: sessions.new_token().unwrap(),
+            label: "operator".to_string(),
+            role: Role::Driver,
+            negotiated: Negotiated {
+                capabilities: Vec::new(),
+                wire_format: WireFormat::Bitcode,
+                video: None,
+            },
+            video_addr: None,
+            parked_at,
+        }
+    }
+
+    #[test]
+    fn tokens_are_unique() {
+        let sessions = ParkedSessions::new();
+        let token = sessions.new_token().unwrap();
+        assert_eq!(token.len(), RESUME_TOKEN_LEN * 2);
+        assert_ne!(token, sessions.new_token().unwrap());
+    }
+
+    #[test]
+    fn unknown_token_resumes_nothing() {
+        let sessions = ParkedSessions::new();
+        sessions.park(parked(&sessions, Instant::now()));
+        assert!(sessions.resume("unknown").is_none());
+        assert!(sessions.resume(&sessions.new_token().unwrap()).is_none());
+    }
+
+    #[test]
+    fn session_resumes_only_once() {
+        let sessions = ParkedSessions::new();
+        let session = parked(&sessions, Instant::now());
+        let token = session.resume_token.clone();
+        sessions.park(session);
+
+        let resumed = sessions.resume(&token).unwrap();
+        assert_eq!(resumed.id, 1);
+        assert_eq!(resumed.role, Role::Driver);
+        assert!(sessions.resume(&token).is_none());
+    }
+
+    #[test]
+    fn stale_expiry_spares_a_parked_again_session() {
+        let sessions = ParkedSessions::new();
+        let first_parked_at = Instant::now();
+        let session = parked(&sessions, first_parked_at);
+        let token = session.resume_token.clone();
+        sessions.park(session);
+
+        // Resumed, then dropped again before the first grace period ran out
+        let mut session = sessions.resume(&token).unwrap();
+        session.parked_at = first_parked_at + Duration::from_millis(10);
+        let second_parked_at = session.parked_at;
+        sessions.park(session);
+
+        assert!(sessions.expire(&token, first_parked_at).is_none());
+        let expired = sessions.expire(&token, second_parked_at).unwrap();
+        assert_eq!(expired.parked_at, second_parked_at);
+        assert!(sessions.resume(&token).is_none());
+    }
+
+    #[test]
+    fn expired_session_cannot_be_resumed() {
+        let sessions = ParkedSessions::new();
+        let parked_at = Instant::now();
+        let session = parked(&sessions, parked_at);
+        let token = session.resume_token.clone();
+        sessions.park(session);
+
+        assert!(sessions.expire(&token, parked_at).is_some());
+        assert!(sessions.resume(&token).is_none());
+        assert!(sessions.expire(&token, parked_at).is_none());
+    }
+}
@@ -0,0 +1,444 @@
+use crate::config::{CommandsConfig, SanitizePolicy};
+use crate::emergency_stop::EmergencyStop;
+use crate::motors::validate_calibration;
+use goliath_common::{Capability, CommandRejection, GoliathCommand, MotorCommand, Role};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+
+/// Checks every command coming from the network before it reaches the motors
+pub(crate) struct CommandValidator {
+    config: CommandsConfig,
+    turret_angles: RangeInclusive<f32>,
+    emergency_stop: Arc<EmergencyStop>,
+    turret_enabled: bool, // Only if both sides support it
+    role: Role,
+}
+
+impl CommandValidator {
+    pub(crate) fn new(
+        config: CommandsConfig,
+        turret_angles: RangeInclusive<f32>,
+        emergency_stop: Arc<EmergencyStop>,
+        turret_enabled: bool,
+        role: Role,
+    ) -> Self {
+        Self {
+            config,
+            turret_angles,
+            emergency_stop,
+            turret_enabled,
+            role,
+        }
+    }
+
+    /// Returns the command to apply, which may have been clamped according to the policy
+    pub(crate) fn validate(
+        &self,
+        cmd: &GoliathCommand,
+    ) -> Result<GoliathCommand, CommandRejection> {
+        if !self.role.permits(cmd) {
+            return Err(CommandRejection::NotPermitted(self.role));
+        }
+
+        if let GoliathCommand::Motor(motor_cmd) = cmd
+            && motor_cmd.is_motion()
+            && self.emergency_stop.is_engaged()
+        {
+            return Err(CommandRejection::EmergencyStopEngaged);
+        }
+
+        if let GoliathCommand::Motor(
+            MotorCommand::TurretAngle(_) | MotorCommand::TurretRate(_) | MotorCommand::TurretHome,
+        ) = cmd
+            && !self.turret_enabled
+        {
+            return Err(CommandRejection::NotNegotiated(Capability::Turret));
+        }
+
+        match cmd {
+            GoliathCommand::Motor(MotorCommand::Thrust(thrust)) => {
+                Self::sanitize(*thrust, -1.0..=1.0, self.config.thrust)
+                    .map(|thrust| GoliathCommand::Motor(MotorCommand::Thrust(thrust)))
+            }
+            GoliathCommand::Motor(MotorCommand::Steer(steer)) => {
+                Self::sanitize(*steer, -1.0..=1.0, self.config.steer)
+                    .map(|steer| GoliathCommand::Motor(MotorCommand::Steer(steer)))
+            }
+            GoliathCommand::Motor(MotorCommand::LeftTrack(power)) => {
+                Self::sanitize(*power, -1.0..=1.0, self.config.track_power)
+                    .map(|power| GoliathCommand::Motor(MotorCommand::LeftTrack(power)))
+            }
+            GoliathCommand::Motor(MotorCommand::RightTrack(power)) => {
+                Self::sanitize(*power, -1.0..=1.0, self.config.track_power)
+                    .map(|power| GoliathCommand::Motor(MotorCommand::RightTrack(power)))
+            }
+            GoliathCommand::Motor(MotorCommand::Drive { thrust, steer }) => {
+                let thrust = Self::sanitize(*thrust, -1.0..=1.0, self.config.thrust)?;
+                let steer = Self::sanitize(*steer, -1.0..=1.0, self.config.steer)?;
+                Ok(GoliathCommand::Motor(MotorCommand::Drive { thrust, steer }))
+            }
+            GoliathCommand::Motor(MotorCommand::Tank { left, right }) => {
+                let left = Self::sanitize(*left, -1.0..=1.0, self.config.track_power)?;
+                let right = Self::sanitize(*right, -1.0..=1.0, self.config.track_power)?;
+                Ok(GoliathCommand::Motor(MotorCommand::Tank { left, right }))
+            }
+            GoliathCommand::Motor(MotorCommand::TurretAngle(angle)) => {
+                Self::sanitize(*angle, self.turret_angles.clone(), self.config.turret_angle)
+                    .map(|angle| GoliathCommand::Motor(MotorCommand::TurretAngle(angle)))
+            }
+            GoliathCommand::Motor(MotorCommand::TurretRate(rate)) => {
+                Self::sanitize(*rate, -1.0..=1.0, self.config.turret_rate)
+                    .map(|rate| GoliathCommand::Motor(MotorCommand::TurretRate(rate)))
+            }
+            GoliathCommand::Motor(MotorCommand::Calibrate { calibration, .. }) => {
+                validate_calibration(calibration)
+                    .map(|()| cmd.clone())
+                    .map_err(CommandRejection::InvalidCalibration)
+            }
+            cmd => Ok(cmd.clone()),
+        }
+    }
+
+    fn sanitize(
+        value: f32,
+        range: RangeInclusive<f32>,
+        policy: SanitizePolicy,
+    ) -> Result<f32, CommandRejection> {
+        if !value.is_finite() {
+            return Err(CommandRejection::NotFinite);
+        }
+
+        if range.contains(&value) {
+            return Ok(value);
+        }
+
+        match policy {
+            SanitizePolicy::Reject => Err(CommandRejection::OutOfRange {
+                min: *range.start(),
+                max: *range.end(),
+            }),
+            SanitizePolicy::Clamp => {
+                let clamped = value.clamp(*range.start(), *range.end());
+                log::warn!("Clamped command value {value} to {clamped}");
+                Ok(clamped)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use goliath_common::{DriveMode, Track, TrackCalibration};
+
+    const NON_FINITE: [f32; 3] = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY];
+
+    fn config(policy: SanitizePolicy) -> CommandsConfig {
+        CommandsConfig {
+            thrust: policy,
+            steer: policy,
+            track_power: policy,
+            turret_angle: policy,
+            turret_rate: policy,
+            ..CommandsConfig::default()
+        }
+    }
+
+    fn validator(config: CommandsConfig, turret_enabled: bool, role: Role) -> CommandValidator {
+        CommandValidator::new(
+            config,
+            -90.0..=90.0,
+            Arc::new(EmergencyStop::new()),
+            turret_enabled,
+            role,
+        )
+    }
+
+    fn driver(policy: SanitizePolicy) -> CommandValidator {
+        validator(config(policy), true, Role::Driver)
+    }
+
+    fn motor(cmd: MotorCommand) -> GoliathCommand {
+        GoliathCommand::Motor(cmd)
+    }
+
+    /// Every command carrying values, with `value` in each of its fields in turn
+    fn valued_commands(value: f32) -> Vec<GoliathCommand> {
+        [
+            MotorCommand::Thrust(value),
+            MotorCommand::Steer(value),
+            MotorCommand::LeftTrack(value),
+            MotorCommand::RightTrack(value),
+            MotorCommand::Drive {
+                thrust: value,
+                steer: 0.0,
+            },
+            MotorCommand::Drive {
+                thrust: 0.0,
+                steer: value,
+            },
+            MotorCommand::Tank {
+                left: value,
+                right: 0.0,
+            },
+            MotorCommand::Tank {
+                left: 0.0,
+                right: value,
+            },
+            MotorCommand::TurretAngle(value),
+            MotorCommand::TurretRate(value),
+        ]
+        .into_iter()
+        .map(motor)
+        .collect()
+    }
+
+    #[test]
+    fn non_finite_values_are_rejected_whatever_the_policy() {
+        for policy in [SanitizePolicy::Reject, SanitizePolicy::Clamp] {
+            let validator = driver(policy);
+            for value in NON_FINITE {
+                for cmd in valued_commands(value) {
+                    assert_eq!(
+                        validator.validate(&cmd),
+                        Err(CommandRejection::NotFinite),
+                        "{cmd:?} with {policy:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn values_in_range_pass_unchanged() {
+        for policy in [SanitizePolicy::Reject, SanitizePolicy::Clamp] {
+            let validator = driver(policy);
+            for value in [-1.0, -0.5, 0.0, 0.5, 1.0] {
+                for cmd in valued_commands(value) {
+                    assert_eq!(validator.validate(&cmd), Ok(cmd.clone()), "{policy:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reject_policy_refuses_out_of_range_values() {
+        let validator = driver(SanitizePolicy::Reject);
+        let out_of_range = CommandRejection::OutOfRange {
+            min: -1.0,
+            max: 1.0,
+        };
+        for value in [-1.5, 1.5] {
+            // The turret angle has a range of its own
+            let commands = valued_commands(value)
+                .into_iter()
+                .filter(|cmd| !matches!(cmd, GoliathCommand::Motor(MotorCommand::TurretAngle(_))));
+            for cmd in commands {
+                assert_eq!(
+                    validator.validate(&cmd),
+                    Err(out_of_range.clone()),
+                    "{cmd:?}"
+                );
+            }
+        }
+        assert_eq!(
+            validator.validate(&motor(MotorCommand::TurretAngle(120.0))),
+            Err(CommandRejection::OutOfRange {
+                min: -90.0,
+                max: 90.0
+            })
+        );
+    }
+
+    #[test]
+    fn clamp_policy_limits_out_of_range_values() {
+        let validator = driver(SanitizePolicy::Clamp);
+        let cases = [
+            (MotorCommand::Thrust(1.5), MotorCommand::Thrust(1.0)),
+            (MotorCommand::Steer(-2.0), MotorCommand::Steer(-1.0)),
+            (MotorCommand::LeftTrack(3.0), MotorCommand::LeftTrack(1.0)),
+            (
+                MotorCommand::RightTrack(-3.0),
+                MotorCommand::RightTrack(-1.0),
+            ),
+            (
+                MotorCommand::TurretAngle(120.0),
+                MotorCommand::TurretAngle(90.0),
+            ),
+            (
+                MotorCommand::TurretAngle(-400.0),
+                MotorCommand::TurretAngle(-90.0),
+            ),
+            (
+                MotorCommand::TurretRate(-1.5),
+                MotorCommand::TurretRate(-1.0),
+            ),
+        ];
+        for (cmd, clamped) in cases {
+            assert_eq!(validator.validate(&motor(cmd)), Ok(motor(clamped)));
+        }
+    }
+
+    #[test]
+    fn drive_fields_follow_their_own_policy() {
+        let validator = validator(
+            CommandsConfig {
+                thrust: SanitizePolicy::Clamp,
+                steer: SanitizePolicy::Reject,
+                ..CommandsConfig::default()
+            },
+            true,
+            Role::Driver,
+        );
+        assert_eq!(
+            validator.validate(&motor(MotorCommand::Drive {
+                thrust: 2.0,
+                steer: 0.5
+            })),
+            Ok(motor(MotorCommand::Drive {
+                thrust: 1.0,
+                steer: 0.5
+            }))
+        );
+        assert!(
+            validator
+                .validate(&motor(MotorCommand::Drive {
+                    thrust: 0.5,
+                    steer: 2.0
+                }))
+                .is_err()
+        );
+        // Clamping one field doesn't save the other
+        assert!(
+            validator
+                .validate(&motor(MotorCommand::Drive {
+                    thrust: 2.0,
+                    steer: f32::NAN
+                }))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn tank_fields_are_sanitized_individually() {
+        let validator = driver(SanitizePolicy::Clamp);
+        assert_eq!(
+            validator.validate(&motor(MotorCommand::Tank {
+                left: -2.0,
+                right: 0.3
+            })),
+            Ok(motor(MotorCommand::Tank {
+                left: -1.0,
+                right: 0.3
+            }))
+        );
+        assert_eq!(
+            validator.validate(&motor(MotorCommand::Tank {
+                left: 0.3,
+                right: 2.0
+            })),
+            Ok(motor(MotorCommand::Tank {
+                left: 0.3,
+                right: 1.0
+            }))
+        );
+    }
+
+    #[test]
+    fn emergency_stop_only_rejects_motion() {
+        let emergency_stop = Arc::new(EmergencyStop::new());
+        let validator = CommandValidator::new(
+            config(SanitizePolicy::Reject),
+            -90.0..=90.0,
+            Arc::clone(&emergency_stop),
+            true,
+            Role::Driver,
+        );
+        emergency_stop.engage();
+
+        for cmd in valued_commands(0.5)
+            .into_iter()
+            .chain([motor(MotorCommand::TurretHome)])
+        {
+            assert_eq!(
+                validator.validate(&cmd),
+                Err(CommandRejection::EmergencyStopEngaged),
+                "{cmd:?}"
+            );
+        }
+        for cmd in [
+            GoliathCommand::ClearEmergencyStop,
+            GoliathCommand::EmergencyStop,
+            GoliathCommand::Heartbeat,
+            GoliathCommand::TakeControl,
+            motor(MotorCommand::Heartbeat),
+            motor(MotorCommand::SetDriveMode(DriveMode::Tank)),
+        ] {
+            assert_eq!(validator.validate(&cmd), Ok(cmd.clone()));
+        }
+
+        emergency_stop.clear();
+        assert!(
+            validator
+                .validate(&motor(MotorCommand::Thrust(0.5)))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn turret_needs_the_capability() {
+        let validator = validator(config(SanitizePolicy::Reject), false, Role::Driver);
+        for cmd in [
+            MotorCommand::TurretAngle(10.0),
+            MotorCommand::TurretRate(0.5),
+            MotorCommand::TurretHome,
+        ] {
+            assert_eq!(
+                validator.validate(&motor(cmd)),
+                Err(CommandRejection::NotNegotiated(Capability::Turret))
+            );
+        }
+        assert!(
+            validator
+                .validate(&motor(MotorCommand::Thrust(0.5)))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn observers_may_only_stop() {
+        let validator = validator(config(SanitizePolicy::Clamp), true, Role::Observer);
+        for cmd in valued_commands(0.5).into_iter().chain([
+            GoliathCommand::Heartbeat,
+            GoliathCommand::ClearEmergencyStop,
+            GoliathCommand::TakeControl,
+        ]) {
+            assert_eq!(
+                validator.validate(&cmd),
+                Err(CommandRejection::NotPermitted(Role::Observer)),
+                "{cmd:?}"
+            );
+        }
+        assert_eq!(
+            validator.validate(&GoliathCommand::EmergencyStop),
+            Ok(GoliathCommand::EmergencyStop)
+        );
+    }
+
+    #[test]
+    fn invalid_calibration_is_rejected() {
+        let validator = driver(SanitizePolicy::Clamp);
+        let calibration = TrackCalibration {
+            trim: 0.0,
+            ..TrackCalibration::default()
+        };
+        let cmd = motor(MotorCommand::Calibrate {
+            track: Track::Left,
+            calibration,
+        });
+        assert!(matches!(
+            validator.validate(&cmd),
+            Err(CommandRejection::InvalidCalibration(_))
+        ));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::GoliathVehicleResult;
+use crate::config::DisplayConfig;
 use crate::ssd1306::commands::{AddressingMode, VComHDeselectLevel};
 use jetgpio::I2c;
 use jetgpio::i2c::bus::I2cBus;
@@ -187,9 +188,10 @@ impl SSD1306Builder {
     }
 }
 
-pub(crate) fn create_ssd_connection() -> GoliathVehicleResult<SSD1306> {
+pub(crate) fn create_ssd_connection(config: &DisplayConfig) -> GoliathVehicleResult<SSD1306> {
+    // The config only allows the one bus the Orin exposes
     let mut i2c = I2c::init(I2cBus::I2c1, 0)?;
-    i2c.set_slave_address(0x3C);
+    i2c.set_slave_address(u32::from(config.address));
 
     let mut ssd = SSD1306::builder(i2c, 128, 32)
         .with_oscillator_frequency(0x8)
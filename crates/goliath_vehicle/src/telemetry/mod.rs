@@ -0,0 +1,22 @@
+use crate::config::TelemetryConfig;
+use std::fs;
+
+const LOAD_AVERAGE_PATH: &str = "/proc/loadavg";
+
+/// CPU temperature in Celsius, None if the thermal zone can't be read
+pub(crate) fn read_cpu_temperature(config: &TelemetryConfig) -> Option<f32> {
+    // Given in millidegrees
+    fs::read_to_string(&config.cpu_temperature_path)
+        .inspect_err(|err| log::debug!("Failed to read CPU temperature: {err}"))
+        .ok()
+        .and_then(|temp| temp.trim().parse::<f32>().ok())
+        .map(|millidegrees| millidegrees / 1000.0)
+}
+
+/// Load average over the last minute, None if it can't be read
+pub(crate) fn read_cpu_load() -> Option<f32> {
+    fs::read_to_string(LOAD_AVERAGE_PATH)
+        .inspect_err(|err| log::debug!("Failed to read CPU load: {err}"))
+        .ok()
+        .and_then(|load| load.split_whitespace().next()?.parse::<f32>().ok())
+}
@@ -7,8 +7,9 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 // Note that the camera provides 2 views, so the resolution is *doubled* in width
-#[derive(Copy, Clone)]
-#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lower")]
 pub(crate) enum ZedCamCaps {
     UHD2K15, // 2K resolution at 15 FPS
 
@@ -64,6 +65,7 @@ pub(crate) struct CapturePipeline {
 
 impl CapturePipeline {
     pub(crate) fn try_new(
+        device: &str,
         capture_caps: ZedCamCaps,
         encoding_pipline: Arc<EncodingPipline>,
     ) -> GoliathVehicleResult<Self> {
@@ -75,7 +77,7 @@ impl CapturePipeline {
 
         let src = gstreamer::ElementFactory::make("v4l2src")
             .name("camera_source")
-            .property("device", "/dev/video0")
+            .property("device", device)
             .property("do-timestamp", true)
             .build()?;
 
@@ -7,7 +7,9 @@ use gstreamer_app::gst;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-#[allow(dead_code)]
+#[derive(Copy, Clone, Debug, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+#[value(rename_all = "lower")]
 pub(crate) enum EncoderType {
     Software,
     V4L2,
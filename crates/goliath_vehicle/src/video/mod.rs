@@ -1,3 +1,4 @@
 pub(crate) mod capture_pipeline;
 pub(crate) mod encoding_pipeline;
 pub(crate) mod rtp_pipeline;
+pub(crate) mod streamer;
@@ -1,19 +1,22 @@
 use crate::error::GoliathVehicleResult;
 use goliath_common::{GoliathGstAppsrc, GoliathGstPipeline, GoliathVideoError, PipelineWrapper};
 use gstreamer::ClockTime;
-use gstreamer::prelude::{Cast, ElementExt, ElementExtManual, GstBinExtManual};
+use gstreamer::prelude::{Cast, ElementExt, ElementExtManual, GstBinExtManual, ObjectExt};
 use gstreamer_app::gst;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 
 pub(crate) struct RTPPipeline {
     pipeline: PipelineWrapper,
     appsrc: gstreamer_app::AppSrc,
+    udpsink: gstreamer::Element,
     started: AtomicBool,
     stopped: AtomicBool,
 }
 
 impl RTPPipeline {
-    pub(crate) fn try_new(output_uris: Vec<(String, u16)>) -> GoliathVehicleResult<Self> {
+    /// Starts without clients, they are added as operators connect
+    pub(crate) fn try_new(payload_type: u8, ssrc: u32) -> GoliathVehicleResult<Self> {
         let pipeline = gstreamer::Pipeline::builder()
             .name("RTPPipeline")
             .async_handling(false)
@@ -29,18 +32,13 @@ impl RTPPipeline {
         let rtp264pay = gstreamer::ElementFactory::make("rtph264pay")
             .name("rtp_payloader")
             .property("config-interval", 1)
+            .property("pt", u32::from(payload_type))
+            .property("ssrc", ssrc)
             .build()?;
 
-        let clients_list = output_uris
-            .into_iter()
-            .map(|(ip, port)| format!("{ip}:{port}"))
-            .collect::<Vec<_>>()
-            .join(",");
-
-        log::info!("Sending to clients: {clients_list:?}");
         let udpsink = gstreamer::ElementFactory::make("udpsink")
             .name("udp_sink")
-            .property("clients", clients_list)
+            .property("clients", "") // Would default to localhost
             .property("sync", false)
             .property("async", false)
             .build()?;
@@ -53,12 +51,29 @@ impl RTPPipeline {
         Ok(Self {
             pipeline: PipelineWrapper::wrap(pipeline),
             appsrc,
+            udpsink,
             started: AtomicBool::new(false),
             stopped: AtomicBool::new(false),
         })
     }
 }
 
+impl RTPPipeline {
+    /// Adding the same client twice needs two removals to stop sending to it
+    /// IPv6 clients are sent to from a separate socket, udpsink opens it as needed
+    pub(crate) fn add_client(&self, addr: SocketAddr) {
+        log::info!("Sending video to {addr}");
+        self.udpsink
+            .emit_by_name::<()>("add", &[&addr.ip().to_string(), &i32::from(addr.port())]);
+    }
+
+    pub(crate) fn remove_client(&self, addr: SocketAddr) {
+        log::info!("No longer sending video to {addr}");
+        self.udpsink
+            .emit_by_name::<()>("remove", &[&addr.ip().to_string(), &i32::from(addr.port())]);
+    }
+}
+
 impl GoliathGstPipeline for RTPPipeline {
     fn get_pipeline(&self) -> &gstreamer::Pipeline {
         self.pipeline.as_ref()
@@ -0,0 +1,88 @@
+use crate::config::VideoConfig;
+use crate::error::GoliathVehicleError;
+use crate::error::GoliathVehicleResult;
+use crate::video::capture_pipeline::CapturePipeline;
+use crate::video::encoding_pipeline::EncodingPipline;
+use crate::video::rtp_pipeline::RTPPipeline;
+use goliath_common::{GoliathGstPipeline, VideoCodec, VideoOffer, VideoState, VideoTransport};
+use gstreamer::prelude::ElementExtManual;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// What the encoders produce, in order of preference
+const VEHICLE_VIDEO_CODECS: [VideoCodec; 1] = [VideoCodec::H264];
+
+/// The vehicle's single camera stream, sent to every connected operator
+pub(crate) struct VideoStreamer {
+    capture_pipeline: Arc<CapturePipeline>,
+    rtp_pipeline: Arc<RTPPipeline>,
+    payload_type: u8,
+    ssrc: u32, // Random, so a restarted vehicle's stream isn't mistaken for the previous one
+    started: AtomicBool,
+}
+
+impl VideoStreamer {
+    pub(crate) fn try_new(config: &VideoConfig) -> GoliathVehicleResult<Self> {
+        let mut ssrc = [0; 4];
+        SystemRandom::new().fill(&mut ssrc).map_err(|_| {
+            GoliathVehicleError::GeneralError("Failed to generate an RTP SSRC".to_string())
+        })?;
+        let ssrc = u32::from_be_bytes(ssrc);
+        let rtp_pipeline = Arc::new(RTPPipeline::try_new(config.payload_type, ssrc)?);
+        let encoding_pipeline = Arc::new(EncodingPipline::try_new(
+            config.encoder,
+            Arc::clone(&rtp_pipeline),
+        )?);
+        let capture_pipeline = Arc::new(CapturePipeline::try_new(
+            &config.device,
+            config.capture_caps,
+            encoding_pipeline,
+        )?);
+
+        Ok(Self {
+            capture_pipeline,
+            rtp_pipeline,
+            payload_type: config.payload_type,
+            ssrc,
+            started: AtomicBool::new(false),
+        })
+    }
+
+    /// The same stream for every operator, only the port is theirs
+    pub(crate) fn accept(&self, offer: &VideoOffer) -> Result<VideoTransport, String> {
+        if offer.port == 0 {
+            return Err("Video port must be positive".to_string());
+        }
+        offer.accept(&VEHICLE_VIDEO_CODECS, self.payload_type, self.ssrc)
+    }
+
+    /// The camera is only started once the first operator wants the video, then keeps running
+    pub(crate) fn add_client(&self, addr: SocketAddr) -> GoliathVehicleResult<()> {
+        self.rtp_pipeline.add_client(addr);
+        if !self.started.swap(true, Ordering::Relaxed) {
+            log::info!("Starting the camera");
+            self.capture_pipeline.start_pipeline(None)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn remove_client(&self, addr: SocketAddr) {
+        self.rtp_pipeline.remove_client(addr);
+    }
+
+    pub(crate) fn state(&self) -> VideoState {
+        match self.capture_pipeline.get_pipeline().current_state() {
+            gstreamer::State::Ready => VideoState::Ready,
+            gstreamer::State::Paused => VideoState::Paused,
+            gstreamer::State::Playing => VideoState::Playing,
+            _ => VideoState::Stopped,
+        }
+    }
+
+    pub(crate) fn stop(&self) {
+        self.capture_pipeline.stop_pipeline().ok();
+    }
+}